@@ -0,0 +1,517 @@
+//! The manifest describing the benchmarks in the workspace: their programs for each target,
+//! their inputs, the results they're expected to produce and the parameters they're swept over.
+
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// The name of the manifest in the root of the workspace.
+pub const MANIFEST_FILE_NAME: &str = "benchmarks.toml";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The directory the paths in the manifest are relative to.
+    #[serde(skip)]
+    pub root: PathBuf,
+
+    #[serde(default, rename = "benchmark")]
+    pub benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Benchmark {
+    pub name: String,
+    /// The directory with the sources of the benchmark.
+    pub directory: PathBuf,
+    pub pvm: Option<PvmTarget>,
+    pub native: Option<NativeTarget>,
+    pub evm: Option<EvmTarget>,
+    #[serde(default, rename = "input")]
+    pub inputs: Vec<Input>,
+    pub sweep: Option<Sweep>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PvmTarget {
+    /// The program blob.
+    pub program: PathBuf,
+    /// The directory with the `build.sh` script which builds the program.
+    pub build: Option<PathBuf>,
+    /// The export to call.
+    #[serde(default = "default_export")]
+    pub export: String,
+    /// The export which reads the input through the `read_input` host call instead of
+    /// from memory, if the program has one.
+    pub streaming_export: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NativeTarget {
+    /// The crate of the native program. It's run with the path to the input as its first
+    /// argument, and the value of the swept parameter, if any, as its second.
+    #[serde(rename = "crate")]
+    pub crate_directory: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvmTarget {
+    /// The runtime bytecode of the contract.
+    pub code: PathBuf,
+    /// The calldata of the contract, as a hex string.
+    pub input: PathBuf,
+    /// The directory with the `build.sh` script which builds the contract.
+    pub build: Option<PathBuf>,
+    /// The gas limit the contract is run with.
+    #[serde(default = "default_evm_gas")]
+    pub gas: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    pub name: String,
+    /// The file with the input; mutually exclusive with `generate`.
+    pub path: Option<PathBuf>,
+    /// How to generate the input; mutually exclusive with `path`.
+    pub generate: Option<Generator>,
+    /// The result the program is expected to return for this input.
+    #[serde(default, deserialize_with = "deserialize_result")]
+    pub expected_result: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Generator {
+    /// Pseudorandom bytes from a SplitMix64 generator with the given seed.
+    Random {
+        size: u64,
+        #[serde(default)]
+        seed: u64,
+    },
+    /// All zeros.
+    Zeros { size: u64 },
+}
+
+/// A parameter the benchmark is run with in addition to its input.
+///
+/// The PVM program receives the value as a little-endian `u64` in front of the input,
+/// and the native program as its second argument.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sweep {
+    /// The name of the parameter, e.g. "max_steps".
+    pub parameter: String,
+    /// The export of the PVM program which takes the parameter.
+    pub export: String,
+    #[serde(rename = "value")]
+    pub values: Vec<SweepValue>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SweepValue {
+    pub name: String,
+    pub value: u64,
+    /// The result the program is expected to return with this value; overrides the one of the input.
+    #[serde(default, deserialize_with = "deserialize_result")]
+    pub expected_result: Option<u64>,
+}
+
+fn default_export() -> String {
+    String::from("run")
+}
+
+fn default_evm_gas() -> u64 {
+    1_000_000_000_000
+}
+
+/// Accepts the results either as integers, or as strings in decimal or in hex prefixed with "0x",
+/// since TOML's integers are signed and can't hold every `u64`.
+fn deserialize_result<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Integer(u64),
+        String(String),
+    }
+
+    let value = match Option::<Value>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Value::Integer(value)) => value,
+        Some(Value::String(value)) => match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|error| serde::de::Error::custom(format!("invalid result '{value}': {error}")))?,
+    };
+
+    Ok(Some(value))
+}
+
+impl Generator {
+    pub fn size(&self) -> u64 {
+        match *self {
+            Generator::Random { size, .. } | Generator::Zeros { size } => size,
+        }
+    }
+
+    pub fn generate(&self) -> Vec<u8> {
+        match *self {
+            Generator::Random { size, seed } => {
+                let mut state = seed;
+                let mut output = Vec::with_capacity(size as usize + 8);
+                while (output.len() as u64) < size {
+                    state = state.wrapping_add(0x9e3779b97f4a7c15);
+                    let mut value = state;
+                    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+                    value ^= value >> 31;
+                    output.extend_from_slice(&value.to_le_bytes());
+                }
+
+                output.truncate(size as usize);
+                output
+            }
+            Generator::Zeros { size } => vec![0; size as usize],
+        }
+    }
+
+    /// Returns a name for the generated data which changes whenever the data does.
+    fn file_name(&self) -> String {
+        match *self {
+            Generator::Random { size, seed } => format!("random-{size}-{seed}.input"),
+            Generator::Zeros { size } => format!("zeros-{size}.input"),
+        }
+    }
+}
+
+impl Manifest {
+    /// Loads the manifest, resolving the paths in it relative to the directory it's in.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("failed to read {path:?}: {error}"))?;
+        let mut manifest: Manifest = toml::from_str(&contents)
+            .map_err(|error| format!("failed to parse {path:?}: {error}"))?;
+
+        manifest.root = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+            _ => PathBuf::from("."),
+        };
+
+        manifest
+            .validate()
+            .map_err(|error| format!("invalid manifest {path:?}: {error}"))?;
+        Ok(manifest)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut names = HashSet::new();
+        for benchmark in &self.benchmarks {
+            if !names.insert(&benchmark.name) {
+                return Err(format!("duplicate benchmark '{}'", benchmark.name));
+            }
+
+            benchmark
+                .validate()
+                .map_err(|error| format!("benchmark '{}': {error}", benchmark.name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Resolves a path from the manifest.
+    pub fn path(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Benchmark> {
+        self.benchmarks
+            .iter()
+            .find(|benchmark| benchmark.name == name)
+    }
+}
+
+impl Benchmark {
+    fn validate(&self) -> Result<(), String> {
+        if self.inputs.is_empty() && (self.pvm.is_some() || self.native.is_some()) {
+            return Err("no inputs".into());
+        }
+
+        let mut names = HashSet::new();
+        for input in &self.inputs {
+            if !names.insert(&input.name) {
+                return Err(format!("duplicate input '{}'", input.name));
+            }
+
+            if input.path.is_some() == input.generate.is_some() {
+                return Err(format!(
+                    "input '{}' needs exactly one of 'path' and 'generate'",
+                    input.name
+                ));
+            }
+        }
+
+        if let Some(ref sweep) = self.sweep {
+            let mut names = HashSet::new();
+            for value in &sweep.values {
+                if !names.insert(&value.name) {
+                    return Err(format!(
+                        "duplicate value '{}' of '{}'",
+                        value.name, sweep.parameter
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn input(&self, name: &str) -> Option<&Input> {
+        self.inputs.iter().find(|input| input.name == name)
+    }
+
+    /// Returns the result the program is expected to return for the given input and value
+    /// of the swept parameter, if it's known.
+    pub fn expected_result(&self, input: &Input, value: Option<&SweepValue>) -> Option<u64> {
+        value
+            .and_then(|value| value.expected_result)
+            .or(input.expected_result)
+    }
+}
+
+impl Input {
+    /// Returns the path to the file with the input, generating it into the cache directory
+    /// first if necessary.
+    pub fn materialize(
+        &self,
+        manifest: &Manifest,
+        benchmark: &Benchmark,
+        cache_directory: &Path,
+    ) -> Result<PathBuf, String> {
+        if let Some(ref path) = self.path {
+            return Ok(manifest.path(path));
+        }
+
+        let generator = self.generate.as_ref().unwrap();
+        let path = cache_directory.join(format!(
+            "{}-{}-{}",
+            benchmark.name,
+            self.name,
+            generator.file_name()
+        ));
+
+        let is_cached = std::fs::metadata(&path)
+            .map(|metadata| metadata.len() == generator.size())
+            .unwrap_or(false);
+        if !is_cached {
+            std::fs::create_dir_all(cache_directory)
+                .map_err(|error| format!("failed to create {cache_directory:?}: {error}"))?;
+            std::fs::write(&path, generator.generate())
+                .map_err(|error| format!("failed to write {path:?}: {error}"))?;
+        }
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<Manifest, String> {
+        let manifest: Manifest = toml::from_str(contents).map_err(|error| error.to_string())?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    fn expected_result(value: &str) -> Result<Option<u64>, String> {
+        let manifest = parse(&format!(
+            r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+
+            [[benchmark.input]]
+            name = "small"
+            path = "small.input"
+            {value}
+            "#
+        ))?;
+        Ok(manifest.benchmarks[0].inputs[0].expected_result)
+    }
+
+    #[test]
+    fn results_can_be_integers_or_strings() {
+        assert_eq!(expected_result(""), Ok(None));
+        assert_eq!(expected_result("expected_result = 42"), Ok(Some(42)));
+        assert_eq!(expected_result("expected_result = \"42\""), Ok(Some(42)));
+        assert_eq!(expected_result("expected_result = \"0x2a\""), Ok(Some(42)));
+        assert_eq!(
+            expected_result("expected_result = \"0xffffffffffffffff\""),
+            Ok(Some(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn invalid_results_are_rejected() {
+        let error = expected_result("expected_result = \"0xzz\"").unwrap_err();
+        assert!(error.contains("invalid result '0xzz'"), "{error}");
+        assert!(expected_result("expected_result = \"forty-two\"").is_err());
+        assert!(expected_result("expected_result = -1").is_err());
+    }
+
+    #[test]
+    fn duplicate_benchmarks_are_rejected() {
+        let error = parse(
+            r#"
+            [[benchmark]]
+            name = "test"
+            directory = "a"
+
+            [[benchmark]]
+            name = "test"
+            directory = "b"
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(error, "duplicate benchmark 'test'");
+    }
+
+    #[test]
+    fn benchmarks_with_programs_need_inputs() {
+        let error = parse(
+            r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+            pvm = { program = "test.polkavm" }
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(error, "benchmark 'test': no inputs");
+    }
+
+    #[test]
+    fn duplicate_inputs_are_rejected() {
+        let error = parse(
+            r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+
+            [[benchmark.input]]
+            name = "small"
+            path = "a.input"
+
+            [[benchmark.input]]
+            name = "small"
+            path = "b.input"
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(error, "benchmark 'test': duplicate input 'small'");
+    }
+
+    #[test]
+    fn inputs_need_exactly_one_source() {
+        let neither = r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+
+            [[benchmark.input]]
+            name = "small"
+            "#;
+        let both = r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+
+            [[benchmark.input]]
+            name = "small"
+            path = "small.input"
+            generate = { kind = "zeros", size = 16 }
+            "#;
+
+        for contents in [neither, both] {
+            assert_eq!(
+                parse(contents).unwrap_err(),
+                "benchmark 'test': input 'small' needs exactly one of 'path' and 'generate'"
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_sweep_values_are_rejected() {
+        let error = parse(
+            r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+
+            [benchmark.sweep]
+            parameter = "max_steps"
+            export = "run_with_step_limit"
+
+            [[benchmark.sweep.value]]
+            name = "short"
+            value = 10
+
+            [[benchmark.sweep.value]]
+            name = "short"
+            value = 20
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "benchmark 'test': duplicate value 'short' of 'max_steps'"
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let error = parse(
+            r#"
+            [[benchmark]]
+            name = "test"
+            directory = "test"
+            iterations = 10
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.contains("unknown field `iterations`"), "{error}");
+    }
+
+    #[test]
+    fn the_random_generator_is_deterministic() {
+        let generator = Generator::Random { size: 20, seed: 0 };
+        let data = generator.generate();
+        assert_eq!(data.len(), 20);
+        assert_eq!(data, generator.generate());
+        // The first output of SplitMix64 seeded with zero.
+        assert_eq!(data[..8], 0xe220a8397b1dcdaf_u64.to_le_bytes());
+
+        let other_seed = Generator::Random { size: 20, seed: 1 }.generate();
+        assert_ne!(data, other_seed);
+
+        // A longer input starts with the shorter one.
+        let longer = Generator::Random { size: 64, seed: 0 }.generate();
+        assert_eq!(longer[..20], data[..]);
+    }
+
+    #[test]
+    fn the_zeros_generator() {
+        let generator = Generator::Zeros { size: 5 };
+        assert_eq!(generator.size(), 5);
+        assert_eq!(generator.generate(), vec![0; 5]);
+    }
+}
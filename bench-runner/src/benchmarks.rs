@@ -0,0 +1,127 @@
+use crate::Target;
+use bench_manifest::{Benchmark, Input, Manifest, SweepValue};
+
+/// A single run of a benchmark on one of the targets.
+pub struct Job<'a> {
+    pub benchmark: &'a Benchmark,
+    pub target: Target,
+    pub variant: String,
+    /// The input of the program; `None` for the EVM targets, which have their own.
+    pub input: Option<&'a Input>,
+    /// The value of the swept parameter, if any.
+    pub sweep_value: Option<&'a SweepValue>,
+}
+
+impl<'a> Job<'a> {
+    /// Returns the result the run is expected to produce, if it's known.
+    pub fn expected_result(&self) -> Option<u64> {
+        self.benchmark
+            .expected_result(self.input?, self.sweep_value)
+    }
+}
+
+/// Returns why the benchmark can't run on the given target, if it can't.
+pub fn check_target(
+    manifest: &Manifest,
+    benchmark: &Benchmark,
+    target: Target,
+) -> Result<(), String> {
+    let required = match target {
+        Target::Pvm => match benchmark.pvm {
+            // The program doesn't have to exist if it can be built.
+            Some(ref pvm) => vec![pvm.build.as_ref().unwrap_or(&pvm.program).clone()],
+            None => return Err("no PVM program".into()),
+        },
+        Target::Native => match benchmark.native {
+            Some(ref native) => vec![native.crate_directory.join("Cargo.toml")],
+            None => return Err("no native program".into()),
+        },
+        Target::EvmGeth | Target::EvmEvmone => {
+            let Some(ref evm) = benchmark.evm else {
+                return Err("no EVM contract".into());
+            };
+
+            let mut required = vec![evm.build.as_ref().unwrap_or(&evm.code).clone()];
+            if target == Target::EvmGeth {
+                required.push("external/bin/evm".into());
+            } else {
+                required.push("external/bin/evmc".into());
+                required.push(crate::runner::EVMONE_LIBRARY.into());
+            }
+            required
+        }
+    };
+
+    match required.iter().find(|path| !manifest.path(path).exists()) {
+        Some(path) => Err(format!("{path:?} not found")),
+        None => Ok(()),
+    }
+}
+
+/// Returns every run of the benchmark on the given target.
+pub fn jobs(benchmark: &Benchmark, target: Target) -> Vec<Job<'_>> {
+    if matches!(target, Target::EvmGeth | Target::EvmEvmone) {
+        // The EVM contracts always run to completion.
+        return vec![Job {
+            benchmark,
+            target,
+            variant: String::from("full"),
+            input: None,
+            sweep_value: None,
+        }];
+    }
+
+    let sweep_values: Vec<Option<&SweepValue>> = match benchmark.sweep {
+        Some(ref sweep) => sweep.values.iter().map(Some).collect(),
+        None => vec![None],
+    };
+
+    let mut jobs = Vec::new();
+    for sweep_value in sweep_values {
+        for input in &benchmark.inputs {
+            let mut variant: Vec<&str> = sweep_value
+                .iter()
+                .map(|value| value.name.as_str())
+                .collect();
+            if benchmark.inputs.len() > 1 || variant.is_empty() {
+                variant.push(&input.name);
+            }
+
+            jobs.push(Job {
+                benchmark,
+                target,
+                variant: variant.join("-"),
+                input: Some(input),
+                sweep_value,
+            });
+        }
+    }
+
+    jobs
+}
+
+/// Returns the benchmarks from the manifest whose directories are present in the workspace.
+pub fn discover(manifest: &Manifest) -> Result<Vec<&Benchmark>, String> {
+    let directory = manifest.path("benchmarks".as_ref());
+    let entries = std::fs::read_dir(&directory)
+        .map_err(|error| format!("failed to read {directory:?}: {error}"))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|error| format!("failed to read {directory:?}: {error}"))?;
+        let path = entry.path();
+        if path.is_dir()
+            && !manifest
+                .benchmarks
+                .iter()
+                .any(|benchmark| manifest.path(&benchmark.directory) == path)
+        {
+            println!("WARNING: {path:?} isn't listed in the manifest; skipping");
+        }
+    }
+
+    Ok(manifest
+        .benchmarks
+        .iter()
+        .filter(|benchmark| manifest.path(&benchmark.directory).is_dir())
+        .collect())
+}
@@ -0,0 +1,279 @@
+use clap::Parser;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+mod benchmarks;
+mod runner;
+
+use crate::benchmarks::{check_target, discover, jobs, Job};
+use crate::runner::Runner;
+use bench_manifest::{Benchmark, Manifest, MANIFEST_FILE_NAME};
+
+#[derive(Parser, Debug)]
+#[clap(version)]
+enum Args {
+    /// Lists the benchmarks found in the workspace, and which targets they can run on.
+    List(ListArgs),
+
+    /// Runs the benchmarks on the selected targets, and writes the output of every run to
+    /// a log file and a summary of it to "results.jsonl" in the output directory.
+    Run(RunArgs),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Target {
+    /// The program compiled for PolkaVM, run through `pvm-host`.
+    Pvm,
+    /// The program compiled for the host.
+    Native,
+    /// The Solidity contract, run through geth's `evm` tool.
+    EvmGeth,
+    /// The Solidity contract, run through evmone's `evmc` tool.
+    EvmEvmone,
+}
+
+impl Target {
+    const ALL: [Target; 4] = [
+        Target::Pvm,
+        Target::Native,
+        Target::EvmGeth,
+        Target::EvmEvmone,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Pvm => "pvm",
+            Target::Native => "native",
+            Target::EvmGeth => "evm-geth",
+            Target::EvmEvmone => "evm-evmone",
+        }
+    }
+}
+
+/// How `pvm-host` passes the input to the PVM programs.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum PvmInputStrategy {
+    Aux,
+    Heap,
+    ReadOnly,
+    /// Only applies to the programs with a streaming export.
+    HostCall,
+}
+
+impl PvmInputStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            PvmInputStrategy::Aux => "aux",
+            PvmInputStrategy::Heap => "heap",
+            PvmInputStrategy::ReadOnly => "read-only",
+            PvmInputStrategy::HostCall => "host-call",
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ManifestArgs {
+    /// The manifest describing the benchmarks. Defaults to the one in the workspace
+    /// this tool was built in.
+    #[clap(long)]
+    manifest: Option<PathBuf>,
+}
+
+impl ManifestArgs {
+    fn load(&self) -> Result<Manifest, String> {
+        let path = match self.manifest {
+            Some(ref path) => path.clone(),
+            None => Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("..")
+                .join(MANIFEST_FILE_NAME),
+        };
+
+        let path = path
+            .canonicalize()
+            .map_err(|error| format!("failed to find the manifest at {path:?}: {error}"))?;
+        Manifest::load(&path)
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ListArgs {
+    #[clap(flatten)]
+    manifest: ManifestArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    #[clap(flatten)]
+    manifest: ManifestArgs,
+
+    /// Only run this benchmark. Can be specified multiple times. Defaults to all of them.
+    #[clap(short, long = "benchmark", value_name = "NAME")]
+    benchmarks: Vec<String>,
+
+    /// Only run on this target. Can be specified multiple times. Defaults to every target
+    /// which is available.
+    #[clap(short, long = "target", value_enum, value_name = "TARGET")]
+    targets: Vec<Target>,
+
+    /// Only run this variant (e.g. an input size or a step limit). Can be specified multiple times.
+    #[clap(long = "variant", value_name = "NAME")]
+    variants: Vec<String>,
+
+    /// Rebuild the PVM programs and the EVM contracts before running them.
+    #[clap(long)]
+    build: bool,
+
+    /// How to pass the input to the PVM programs. Defaults to the one `pvm-host` uses.
+    #[clap(long, value_enum, value_name = "STRATEGY")]
+    pvm_input_strategy: Option<PvmInputStrategy>,
+
+    /// An extra argument to pass to `pvm-host run` (e.g. "--backend=interpreter").
+    /// Can be specified multiple times.
+    #[clap(long = "pvm-host-arg", value_name = "ARG", allow_hyphen_values = true)]
+    pvm_host_args: Vec<String>,
+
+    /// The directory to write the logs and the results to.
+    #[clap(short, long, default_value = "logs")]
+    output_dir: PathBuf,
+}
+
+fn main_list(args: ListArgs) -> Result<(), String> {
+    let manifest = args.manifest.load()?;
+    for benchmark in discover(&manifest)? {
+        println!("{}:", benchmark.name);
+        for target in Target::ALL {
+            match check_target(&manifest, benchmark, target) {
+                Ok(()) => {
+                    let variants: Vec<String> = jobs(benchmark, target)
+                        .into_iter()
+                        .map(|job| job.variant)
+                        .collect();
+                    println!("  {:<12} {}", target.name(), variants.join(", "));
+                }
+                Err(error) => println!("  {:<12} unavailable: {error}", target.name()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn selected_benchmarks<'a>(
+    manifest: &'a Manifest,
+    names: &[String],
+) -> Result<Vec<&'a Benchmark>, String> {
+    let found = discover(manifest)?;
+    if let Some(name) = names
+        .iter()
+        .find(|name| !found.iter().any(|benchmark| benchmark.name == **name))
+    {
+        return Err(format!("benchmark not found: '{name}'"));
+    }
+
+    Ok(found
+        .into_iter()
+        .filter(|benchmark| names.is_empty() || names.contains(&benchmark.name))
+        .collect())
+}
+
+fn main_run(args: RunArgs) -> Result<(), String> {
+    let manifest = args.manifest.load()?;
+    let targets = if args.targets.is_empty() {
+        Target::ALL.to_vec()
+    } else {
+        args.targets.clone()
+    };
+
+    let mut all_jobs: Vec<Job> = Vec::new();
+    for benchmark in selected_benchmarks(&manifest, &args.benchmarks)? {
+        for &target in &targets {
+            if let Err(error) = check_target(&manifest, benchmark, target) {
+                println!(
+                    "WARNING: skipping {} on {}: {error}",
+                    benchmark.name,
+                    target.name()
+                );
+                continue;
+            }
+
+            all_jobs.extend(
+                jobs(benchmark, target)
+                    .into_iter()
+                    .filter(|job| args.variants.is_empty() || args.variants.contains(&job.variant)),
+            );
+        }
+    }
+
+    if all_jobs.is_empty() {
+        return Err("nothing to run".into());
+    }
+
+    std::fs::create_dir_all(&args.output_dir)
+        .map_err(|error| format!("failed to create {:?}: {error}", args.output_dir))?;
+    let output_dir = args
+        .output_dir
+        .canonicalize()
+        .map_err(|error| format!("failed to find {:?}: {error}", args.output_dir))?;
+
+    let mut runner = Runner::new(
+        &manifest,
+        &output_dir,
+        args.build,
+        args.pvm_input_strategy,
+        &args.pvm_host_args,
+    )?;
+    let mut failures = 0;
+    for (nth, job) in all_jobs.iter().enumerate() {
+        println!(
+            "[{}/{}] Running {} on {} ({})...",
+            nth + 1,
+            all_jobs.len(),
+            job.benchmark.name,
+            job.target.name(),
+            job.variant
+        );
+
+        let record = runner.run(job)?;
+        if record.success {
+            println!("  Finished in {}s", record.wall_time);
+        } else if record.exit_code == Some(0) {
+            failures += 1;
+            println!(
+                "  FAILED: expected a result of {}, got {}; see {}",
+                record.expected_result.as_deref().unwrap_or_default(),
+                record.result.as_deref().unwrap_or("none"),
+                record.log
+            );
+        } else {
+            failures += 1;
+            println!("  FAILED; see {}", record.log);
+        }
+    }
+
+    println!();
+    println!(
+        "{} runs, {failures} failed; results written to {:?}",
+        all_jobs.len(),
+        output_dir.join("results.jsonl")
+    );
+
+    if failures > 0 {
+        return Err(format!("{failures} run(s) failed"));
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    let result = match args {
+        Args::List(args) => main_list(args),
+        Args::Run(args) => main_run(args),
+    };
+
+    if let Err(error) = result {
+        eprintln!("ERROR: {error}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::benchmarks::Job;
+use crate::{PvmInputStrategy, Target};
+use bench_manifest::Manifest;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+pub const EVMONE_LIBRARY: &str = "external/bin/libevmone.so.0.15.0";
+
+/// The outcome of a single run, as written to the results file.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunRecord {
+    pub benchmark: String,
+    pub target: Target,
+    pub variant: String,
+    pub command: String,
+    /// The file the output of the run was written to.
+    pub log: String,
+    /// Whether the run exited successfully and produced the expected result, if one is known.
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub result: Option<String>,
+    pub expected_result: Option<String>,
+    /// How long the run took as a whole, including starting up the process, in seconds.
+    pub wall_time: f64,
+    /// The numbers extracted from the output of the run.
+    pub metrics: BTreeMap<String, f64>,
+}
+
+/// Runs a command to completion, failing if it doesn't succeed.
+fn run_checked(command: &mut Command) -> Result<(), String> {
+    let status = command
+        .status()
+        .map_err(|error| format!("failed to run {command:?}: {error}"))?;
+    if !status.success() {
+        return Err(format!("{command:?} failed: {status}"));
+    }
+
+    Ok(())
+}
+
+pub struct Runner<'a> {
+    pub manifest: &'a Manifest,
+    pub output_dir: &'a Path,
+    /// Whether to rebuild the programs before running them.
+    pub build: bool,
+    /// How `pvm-host` passes the input to the programs, if not the default.
+    pub pvm_input_strategy: Option<PvmInputStrategy>,
+    /// Extra arguments passed to `pvm-host run`.
+    pub pvm_host_args: &'a [String],
+    results: File,
+    /// The programs which were already built, by their directory.
+    built: HashSet<PathBuf>,
+}
+
+impl<'a> Runner<'a> {
+    pub fn new(
+        manifest: &'a Manifest,
+        output_dir: &'a Path,
+        build: bool,
+        pvm_input_strategy: Option<PvmInputStrategy>,
+        pvm_host_args: &'a [String],
+    ) -> Result<Self, String> {
+        let path = output_dir.join("results.jsonl");
+        let results = File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|error| format!("failed to open {path:?}: {error}"))?;
+
+        Ok(Runner {
+            manifest,
+            output_dir,
+            build,
+            pvm_input_strategy,
+            pvm_host_args,
+            results,
+            built: HashSet::new(),
+        })
+    }
+
+    /// Returns the input of the program for the given job, generating it if necessary.
+    fn prepare_input(&self, job: &Job) -> Result<PathBuf, String> {
+        let cache_directory = self.manifest.path("target/bench-inputs".as_ref());
+        job.input
+            .unwrap()
+            .materialize(self.manifest, job.benchmark, &cache_directory)
+    }
+
+    /// Returns the input of the PVM program for the given job, prefixed with the value of
+    /// the swept parameter if there is one.
+    fn prepare_pvm_input(&self, job: &Job) -> Result<PathBuf, String> {
+        let input = self.prepare_input(job)?;
+        let Some(value) = job.sweep_value else {
+            return Ok(input);
+        };
+
+        let data =
+            std::fs::read(&input).map_err(|error| format!("failed to read {input:?}: {error}"))?;
+        let mut prefixed = value.value.to_le_bytes().to_vec();
+        prefixed.extend_from_slice(&data);
+
+        let directory = self.output_dir.join("inputs");
+        std::fs::create_dir_all(&directory)
+            .map_err(|error| format!("failed to create {directory:?}: {error}"))?;
+        let path = directory.join(format!("{}-{}.input", job.benchmark.name, job.variant));
+        std::fs::write(&path, prefixed)
+            .map_err(|error| format!("failed to write {path:?}: {error}"))?;
+
+        Ok(path)
+    }
+
+    /// Runs the build script in the given directory, unless it was already run.
+    fn build(&mut self, directory: Option<&PathBuf>) -> Result<(), String> {
+        let Some(directory) = directory.map(|directory| self.manifest.path(directory)) else {
+            return Ok(());
+        };
+
+        if !self.build || self.built.contains(&directory) {
+            return Ok(());
+        }
+
+        println!("Building {directory:?}...");
+        run_checked(Command::new("./build.sh").current_dir(&directory))?;
+        self.built.insert(directory);
+        Ok(())
+    }
+
+    pub fn run(&mut self, job: &Job) -> Result<RunRecord, String> {
+        let benchmark = job.benchmark;
+        let root = &self.manifest.root;
+
+        let mut command = match job.target {
+            Target::Pvm => {
+                let pvm = benchmark.pvm.as_ref().unwrap();
+                self.build(pvm.build.as_ref())?;
+
+                let export = match (job.sweep_value, &benchmark.sweep) {
+                    (Some(_), Some(sweep)) => &sweep.export,
+                    _ => &pvm.export,
+                };
+
+                // Only the streaming export reads the input through the host call.
+                let mut input_strategy = self.pvm_input_strategy;
+                let export = match (input_strategy, &pvm.streaming_export) {
+                    (Some(PvmInputStrategy::HostCall), Some(streaming_export)) => streaming_export,
+                    (Some(PvmInputStrategy::HostCall), None) => {
+                        println!(
+                            "WARNING: {} has no streaming export; passing the input through the aux data",
+                            benchmark.name
+                        );
+                        input_strategy = None;
+                        export
+                    }
+                    _ => export,
+                };
+
+                let input = self.prepare_pvm_input(job)?;
+                let mut command = Command::new("cargo");
+                command
+                    .current_dir(root)
+                    .args(["run", "--release", "-q", "-p", "pvm-host", "--", "run"])
+                    .arg("--name")
+                    .arg(format!("{}/{}", benchmark.name, job.variant))
+                    .arg("--output")
+                    .arg(self.output_dir.join("pvm-results.jsonl"))
+                    .arg("--export")
+                    .arg(export)
+                    .args(
+                        input_strategy
+                            .map(|strategy| format!("--input-strategy={}", strategy.name())),
+                    )
+                    .args(self.pvm_host_args)
+                    .arg(self.manifest.path(&pvm.program))
+                    .arg(input);
+                command
+            }
+            Target::Native => {
+                let native = benchmark.native.as_ref().unwrap();
+                let input = self.prepare_input(job)?;
+                let mut command = Command::new("cargo");
+                command
+                    .current_dir(self.manifest.path(&native.crate_directory))
+                    .args(["run", "--release", "-q", "--"])
+                    .arg(input);
+                if let Some(value) = job.sweep_value {
+                    command.arg(value.value.to_string());
+                }
+                command
+            }
+            Target::EvmGeth => {
+                let evm = benchmark.evm.as_ref().unwrap();
+                self.build(evm.build.as_ref())?;
+
+                let mut command = Command::new(root.join("external/bin/evm"));
+                command
+                    .current_dir(root)
+                    .args(["run", "--statdump", "--gas"])
+                    .arg(evm.gas.to_string())
+                    .arg("--codefile")
+                    .arg(&evm.code)
+                    .arg("--inputfile")
+                    .arg(&evm.input);
+                command
+            }
+            Target::EvmEvmone => {
+                let evm = benchmark.evm.as_ref().unwrap();
+                self.build(evm.build.as_ref())?;
+
+                let mut command = Command::new(root.join("external/bin/evmc"));
+                command
+                    .current_dir(root)
+                    .args(["--vm", EVMONE_LIBRARY, "run", "--gas"])
+                    .arg(evm.gas.to_string())
+                    .arg(format!("@{}", evm.code.display()))
+                    .arg("--input")
+                    .arg(format!("@{}", evm.input.display()));
+                command
+            }
+        };
+
+        let log = self.output_dir.join(format!(
+            "{}-{}-{}.log",
+            benchmark.name,
+            job.target.name(),
+            job.variant
+        ));
+
+        let instant = Instant::now();
+        let output = command
+            .output()
+            .map_err(|error| format!("failed to run {command:?}: {error}"))?;
+        let wall_time = instant.elapsed().as_secs_f64();
+
+        // Some of the tools print their statistics to stderr, so both are kept.
+        let mut contents = output.stdout;
+        contents.extend_from_slice(&output.stderr);
+        std::fs::write(&log, &contents)
+            .map_err(|error| format!("failed to write {log:?}: {error}"))?;
+
+        let contents = String::from_utf8_lossy(&contents);
+        let results = parse_results(&contents);
+        let expected_result = job.expected_result();
+        let is_expected = match expected_result {
+            Some(expected) => {
+                !results.is_empty() && results.iter().all(|result| *result == expected)
+            }
+            None => true,
+        };
+
+        let record = RunRecord {
+            benchmark: benchmark.name.clone(),
+            target: job.target,
+            variant: job.variant.clone(),
+            command: format!("{command:?}"),
+            log: log.display().to_string(),
+            success: output.status.success() && is_expected,
+            exit_code: output.status.code(),
+            result: results.first().map(|result| format!("0x{result:x}")),
+            expected_result: expected_result.map(|result| format!("0x{result:x}")),
+            wall_time,
+            metrics: parse_metrics(job.target, &contents),
+        };
+
+        let mut line = serde_json::to_string(&record).unwrap();
+        line.push('\n');
+        self.results
+            .write_all(line.as_bytes())
+            .map_err(|error| format!("failed to write the results: {error}"))?;
+
+        Ok(record)
+    }
+}
+
+/// Extracts the results from the output; `pvm-host` prints one for each cost model
+/// as "  Result: 0x...", and the native programs print "[INFO] Result: 0x...".
+fn parse_results(output: &str) -> Vec<u64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, result) = line.split_once("Result: 0x")?;
+            u64::from_str_radix(result.trim(), 16).ok()
+        })
+        .collect()
+}
+
+/// Extracts the numbers the programs and the tools print from their output.
+///
+/// The PVM results aren't parsed here, since `pvm-host` writes them out in full by itself.
+fn parse_metrics(target: Target, output: &str) -> BTreeMap<String, f64> {
+    let mut metrics = BTreeMap::new();
+    for line in output.lines() {
+        let line = line.trim();
+        match target {
+            Target::Pvm => {}
+            // "[INFO] Finished in 123 (0x7b) steps!" or "[INFO] Average time: 0.5s".
+            Target::Native => {
+                let Some(rest) = line.strip_prefix("[INFO] ") else {
+                    continue;
+                };
+
+                if let Some(time) = rest
+                    .strip_prefix("Average time: ")
+                    .and_then(|time| time.strip_suffix('s'))
+                {
+                    if let Ok(time) = time.parse() {
+                        metrics.insert(String::from("average_time"), time);
+                    }
+                } else if let Some(rest) = rest.strip_prefix("Finished in ") {
+                    let steps = rest.split_whitespace().next().unwrap_or_default();
+                    if let Ok(steps) = steps.parse() {
+                        metrics.insert(String::from("steps"), steps);
+                    }
+                }
+            }
+            // "Gas used: 123" for evmc, and "EVM gas used: 123" for geth.
+            Target::EvmGeth | Target::EvmEvmone => {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+
+                if key.to_lowercase().ends_with("gas used") {
+                    if let Ok(gas) = value.trim().parse() {
+                        metrics.insert(String::from("gas_used"), gas);
+                    }
+                }
+            }
+        }
+    }
+
+    metrics
+}
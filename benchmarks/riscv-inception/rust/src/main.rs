@@ -7,7 +7,9 @@ extern crate picoalloc;
 #[cfg(target_env = "polkavm")]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
-    loop {}
+    unsafe {
+        core::arch::asm!("unimp", options(noreturn));
+    }
 }
 
 use core::cmp::Ord;
@@ -15,6 +17,12 @@ use core::panic;
 
 extern crate alloc;
 
+#[cfg(target_env = "polkavm")]
+#[polkavm_derive::polkavm_import]
+extern "C" {
+    fn log(pointer: *const u8, length: usize);
+}
+
 type Reg = usize;
 
 const SP: Reg = 2;
@@ -78,6 +86,11 @@ impl State {
                     let length = self.regs[A2] as usize;
                     let _blob = &self.memory[pointer..pointer + length];
 
+                    #[cfg(target_env = "polkavm")]
+                    unsafe {
+                        log(_blob.as_ptr(), _blob.len());
+                    }
+
                     #[cfg(not(target_env = "polkavm"))]
                     println!("guest> {}", String::from_utf8_lossy(_blob));
                     return Status::Continue;
@@ -335,22 +348,71 @@ impl State {
 
 #[cfg(not(target_env = "polkavm"))]
 fn main() {
-    let calldata = include_bytes!("../../../../blobs/guest-program.bin");
-    let count = run(calldata.as_ptr().addr(), calldata.len());
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("usage: riscv-inception <INPUT> [MAX STEPS]");
+    // The maximum number of steps to execute can be passed as the second argument.
+    let max_steps: Option<u64> = args
+        .next()
+        .map(|arg| arg.parse().expect("invalid step limit"));
+
+    let program = std::fs::read(&path).expect("failed to read the input");
+    let calldata = match max_steps {
+        Some(max_steps) => {
+            let mut calldata = max_steps.to_le_bytes().to_vec();
+            calldata.extend_from_slice(&program);
+            calldata
+        }
+        None => program,
+    };
+
+    let run_once = || match max_steps {
+        Some(_) => run_with_step_limit(calldata.as_ptr().addr(), calldata.len()),
+        None => run(calldata.as_ptr().addr(), calldata.len()),
+    };
+
+    let count = run_once();
 
     let time = std::time::Instant::now();
     for _ in 0..10 {
-        run(calldata.as_ptr().addr(), calldata.len());
+        run_once();
     }
     let elapsed = time.elapsed().as_secs_f64() / 10.0;
 
     println!("[INFO] Finished in {count} (0x{count:x}) steps!");
+    println!("[INFO] Result: 0x{count:x}");
     println!("[INFO] Average time: {elapsed}s",);
 }
 
 #[cfg_attr(target_env = "polkavm", polkavm_derive::polkavm_export)]
 fn run(calldata: usize, length: usize) -> u64 {
-    let mut data = unsafe { core::slice::from_raw_parts(calldata as *const u8, length) }.to_vec();
+    let calldata = unsafe { core::slice::from_raw_parts(calldata as *const u8, length) };
+    execute(calldata, 0)
+}
+
+/// Same as `run`, except the calldata starts with the maximum number of steps to execute
+/// as a little-endian `u64`, where zero means that there's no limit.
+#[cfg_attr(target_env = "polkavm", polkavm_derive::polkavm_export)]
+fn run_with_step_limit(calldata: usize, length: usize) -> u64 {
+    let calldata = unsafe { core::slice::from_raw_parts(calldata as *const u8, length) };
+    let (max_steps, program) = calldata.split_at(8);
+    let max_steps = u64::from_le_bytes([
+        max_steps[0],
+        max_steps[1],
+        max_steps[2],
+        max_steps[3],
+        max_steps[4],
+        max_steps[5],
+        max_steps[6],
+        max_steps[7],
+    ]);
+
+    execute(program, max_steps)
+}
+
+fn execute(program: &[u8], max_steps: u64) -> u64 {
+    let mut data = program.to_vec();
 
     let bss_size = {
         let xs = &data[data.len() - 4..];
@@ -377,6 +439,9 @@ fn run(calldata: usize, length: usize) -> u64 {
             Status::Error => panic!(),
             Status::Finished => break,
         }
+        if count == max_steps {
+            break;
+        }
     }
 
     count
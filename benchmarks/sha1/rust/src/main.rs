@@ -5,7 +5,10 @@
 #[cfg(target_env = "polkavm")]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
-    loop {}
+    // Trap instead of spinning until the gas runs out.
+    unsafe {
+        core::arch::asm!("unimp", options(noreturn));
+    }
 }
 
 use sha1::Digest;
@@ -15,11 +18,41 @@ fn main() {
     run_native();
 }
 
+#[cfg(target_env = "polkavm")]
+#[polkavm_derive::polkavm_import]
+extern "C" {
+    fn read_input(pointer: *mut u8, length: usize, offset: usize) -> usize;
+}
+
 #[cfg_attr(target_env = "polkavm", polkavm_derive::polkavm_export)]
 fn run(calldata: usize, length: usize) -> u64 {
     let calldata = unsafe { core::slice::from_raw_parts(calldata as *const u8, length) };
     let mut hasher = sha1::Sha1::new();
     hasher.update(&calldata);
+    finish(hasher)
+}
+
+/// Like `run`, but pulls the input from the host in chunks instead of reading it from memory.
+#[cfg(target_env = "polkavm")]
+#[polkavm_derive::polkavm_export]
+fn run_streaming(_calldata: usize, length: usize) -> u64 {
+    let mut buffer = [0; 4096];
+    let mut hasher = sha1::Sha1::new();
+    let mut offset = 0;
+    while offset < length {
+        let count = unsafe { read_input(buffer.as_mut_ptr(), buffer.len(), offset) };
+        if count == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..count]);
+        offset += count;
+    }
+
+    finish(hasher)
+}
+
+fn finish(hasher: sha1::Sha1) -> u64 {
     let h = hasher.finalize();
     let h = h.as_slice();
     let h = u64::from_be_bytes([h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7]]);
@@ -29,28 +62,15 @@ fn run(calldata: usize, length: usize) -> u64 {
 
 #[cfg(not(target_env = "polkavm"))]
 fn run_native() -> () {
-    for (size, calldata) in [
-        ("1K", &include_bytes!("../../../../blobs/sha1-1k.input")[..]),
-        (
-            "10K",
-            &include_bytes!("../../../../blobs/sha1-10k.input")[..],
-        ),
-        (
-            "100K",
-            &include_bytes!("../../../../blobs/sha1-100k.input")[..],
-        ),
-        ("1M", &include_bytes!("../../../../blobs/sha1-1m.input")[..]),
-        (
-            "10M",
-            &include_bytes!("../../../../blobs/sha1-10m.input")[..],
-        ),
-    ] {
+    let path = std::env::args().nth(1).expect("usage: sha1-demo <INPUT>");
+    let calldata = std::fs::read(&path).expect("failed to read the input");
+
+    let result = run(calldata.as_ptr().addr(), calldata.len());
+    let time = std::time::Instant::now();
+    for _ in 0..10 {
         run(calldata.as_ptr().addr(), calldata.len());
-        let time = std::time::Instant::now();
-        for _ in 0..10 {
-            run(calldata.as_ptr().addr(), calldata.len());
-        }
-        let elapsed = time.elapsed().as_secs_f64() / 10.0;
-        println!("[{size}] Average time: {elapsed}s",);
     }
+    let elapsed = time.elapsed().as_secs_f64() / 10.0;
+    println!("[INFO] Result: 0x{result:x}");
+    println!("[INFO] Average time: {elapsed}s",);
 }
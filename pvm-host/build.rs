@@ -0,0 +1,38 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Returns the version of the given package as recorded in the workspace's lock file.
+fn locked_version(lock_file: &Path, package: &str) -> Option<String> {
+    let contents = std::fs::read_to_string(lock_file).ok()?;
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() != format!("name = \"{package}\"") {
+            continue;
+        }
+
+        let version = lines.next()?.trim().strip_prefix("version = ")?;
+        return Some(version.trim_matches('"').to_owned());
+    }
+
+    None
+}
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let lock_file = Path::new(&manifest_dir).join("..").join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_file.display());
+
+    let polkavm_version =
+        locked_version(&lock_file, "polkavm").unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=PVM_HOST_POLKAVM_VERSION={polkavm_version}");
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_owned())
+        .unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=PVM_HOST_RUSTC_VERSION={rustc_version}");
+}
@@ -0,0 +1,261 @@
+use crate::CostModelArgs;
+use polkavm::program::{Opcode, ISA64_V1};
+use polkavm::{CostModel, ProgramBlob, ProgramCounter};
+use pvm_host::Error;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeGasArgs {
+    #[clap(flatten)]
+    cost_models: CostModelArgs,
+
+    /// The number of the heaviest basic blocks to list.
+    #[clap(long, default_value_t = 20)]
+    top: usize,
+
+    /// Write the cost of every basic block and export to this file as JSON.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// The program blob to analyze.
+    program: PathBuf,
+}
+
+/// Returns the name of the given opcode, as used in the cost models' JSON.
+pub fn opcode_name(opcode: Opcode) -> String {
+    format!("{opcode:?}")
+}
+
+/// Maps the offset of every instruction in the program to its opcode.
+pub fn opcodes_by_offset(blob: &ProgramBlob) -> HashMap<ProgramCounter, Opcode> {
+    blob.instructions(ISA64_V1)
+        .map(|instruction| (instruction.offset, instruction.opcode()))
+        .collect()
+}
+
+/// A sequence of instructions which is always entered at the start; its whole gas cost is
+/// charged at once when it's entered.
+pub struct BasicBlock {
+    /// The offset of the first instruction.
+    pub offset: ProgramCounter,
+    /// The offset right after the last instruction.
+    pub end: ProgramCounter,
+    pub opcodes: Vec<Opcode>,
+}
+
+impl BasicBlock {
+    /// Returns the static gas cost of this block under the given cost model.
+    pub fn gas(&self, cost_model: &CostModel) -> u64 {
+        self.opcodes
+            .iter()
+            .map(|&opcode| u64::from(cost_model.cost_for_opcode(opcode)))
+            .sum()
+    }
+}
+
+/// Splits the program's code into basic blocks, in the order in which they appear.
+pub fn basic_blocks(blob: &ProgramBlob) -> Vec<BasicBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<BasicBlock> = None;
+    for instruction in blob.instructions(ISA64_V1) {
+        let block = current.get_or_insert_with(|| BasicBlock {
+            offset: instruction.offset,
+            end: instruction.offset,
+            opcodes: Vec::new(),
+        });
+
+        let opcode = instruction.opcode();
+        block.opcodes.push(opcode);
+        block.end = instruction.next_offset;
+        if opcode.starts_new_basic_block() {
+            blocks.extend(current.take());
+        }
+    }
+
+    blocks.extend(current);
+    blocks
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockCost {
+    pub index: usize,
+    pub offset: u32,
+    pub instructions: usize,
+    /// The gas charged for this block under each cost model.
+    pub gas: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportCost {
+    pub name: String,
+    pub offset: u32,
+    /// The gas charged when the export is entered, under each cost model.
+    pub entry_gas: BTreeMap<String, u64>,
+    /// The total gas of all of the blocks between this export and the next one, under each
+    /// cost model.
+    pub region_gas: BTreeMap<String, u64>,
+    pub region_blocks: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GasAnalysis {
+    pub program: String,
+    /// The total gas of all of the blocks in the program, under each cost model.
+    pub total_gas: BTreeMap<String, u64>,
+    pub exports: Vec<ExportCost>,
+    pub blocks: Vec<BlockCost>,
+}
+
+/// Sums up the gas of the given blocks under each cost model.
+fn sum_gas<'a>(
+    blocks: impl Iterator<Item = &'a BlockCost> + Clone,
+    models: &[&str],
+) -> BTreeMap<String, u64> {
+    models
+        .iter()
+        .map(|model| {
+            let total = blocks.clone().map(|block| block.gas[*model]).sum();
+            (model.to_string(), total)
+        })
+        .collect()
+}
+
+pub fn main_analyze_gas(args: AnalyzeGasArgs) -> Result<(), Error> {
+    let raw_blob = std::fs::read(&args.program)
+        .map_err(|error| format!("failed to read {:?}: {error}", args.program))?;
+    let blob = ProgramBlob::parse(raw_blob[..].into())
+        .map_err(|error| format!("failed to parse {:?}: {error}", args.program))?;
+    let cost_models = args.cost_models.load()?;
+
+    let models: Vec<&str> = cost_models
+        .iter()
+        .map(|cost_model| cost_model.name.as_str())
+        .collect();
+
+    let blocks: Vec<BlockCost> = basic_blocks(&blob)
+        .iter()
+        .enumerate()
+        .map(|(index, block)| BlockCost {
+            index,
+            offset: block.offset.0,
+            instructions: block.opcodes.len(),
+            gas: cost_models
+                .iter()
+                .map(|cost_model| (cost_model.name.clone(), block.gas(&cost_model.cost_model)))
+                .collect(),
+        })
+        .collect();
+
+    // There's no record of where a function ends, so every block up until the next export
+    // is attributed to the preceding export. This is only an approximation, since the linker
+    // is free to interleave the code of different functions.
+    let mut export_offsets: Vec<(String, u32)> = blob
+        .exports()
+        .map(|export| (export.symbol().to_string(), export.program_counter().0))
+        .collect();
+    export_offsets.sort_by_key(|(_, offset)| *offset);
+
+    let mut exports = Vec::new();
+    for (nth, (name, offset)) in export_offsets.iter().enumerate() {
+        let region_end = export_offsets
+            .get(nth + 1)
+            .map_or(u32::MAX, |(_, offset)| *offset);
+        let region = blocks
+            .iter()
+            .filter(|block| block.offset >= *offset && block.offset < region_end);
+        let entry_gas = match blocks.iter().find(|block| block.offset == *offset) {
+            Some(block) => block.gas.clone(),
+            None => sum_gas(std::iter::empty(), &models),
+        };
+
+        exports.push(ExportCost {
+            name: name.clone(),
+            offset: *offset,
+            entry_gas,
+            region_gas: sum_gas(region.clone(), &models),
+            region_blocks: region.count(),
+        });
+    }
+
+    let analysis = GasAnalysis {
+        program: args.program.display().to_string(),
+        total_gas: sum_gas(blocks.iter(), &models),
+        exports,
+        blocks,
+    };
+
+    print_analysis(&analysis, &models, args.top);
+
+    if let Some(path) = args.output {
+        let json = serde_json::to_string_pretty(&analysis)
+            .map_err(|error| format!("failed to serialize the analysis: {error}"))?;
+        std::fs::write(&path, json)
+            .map_err(|error| format!("failed to write {path:?}: {error}"))?;
+    }
+
+    Ok(())
+}
+
+fn print_analysis(analysis: &GasAnalysis, models: &[&str], top: usize) {
+    println!("Basic blocks: {}", analysis.blocks.len());
+    println!();
+
+    print!("  {:<40}", "Total");
+    for model in models {
+        print!(" {:>16}", model);
+    }
+    println!();
+    print!("  {:<40}", "");
+    for model in models {
+        print!(" {:>16}", analysis.total_gas[*model]);
+    }
+    println!();
+    println!();
+
+    print!("  {:<30} {:>9} {:>7}", "Export", "Offset", "Blocks");
+    for model in models {
+        print!(
+            " {:>16} {:>16}",
+            format!("{model} entry"),
+            format!("{model} region")
+        );
+    }
+    println!();
+    for export in &analysis.exports {
+        print!(
+            "  {:<30} {:>9} {:>7}",
+            export.name, export.offset, export.region_blocks
+        );
+        for model in models {
+            print!(
+                " {:>16} {:>16}",
+                export.entry_gas[*model], export.region_gas[*model]
+            );
+        }
+        println!();
+    }
+    println!();
+
+    // The heaviest blocks are ranked by the first cost model.
+    let mut heaviest: Vec<&BlockCost> = analysis.blocks.iter().collect();
+    heaviest.sort_by(|lhs, rhs| rhs.gas[models[0]].cmp(&lhs.gas[models[0]]));
+
+    println!("Heaviest blocks according to {}:", models[0]);
+    print!("  {:>7} {:>9} {:>12}", "Block", "Offset", "Instructions");
+    for model in models {
+        print!(" {:>16}", model);
+    }
+    println!();
+    for block in heaviest.into_iter().take(top) {
+        print!(
+            "  {:>7} {:>9} {:>12}",
+            block.index, block.offset, block.instructions
+        );
+        for model in models {
+            print!(" {:>16}", block.gas[*model]);
+        }
+        println!();
+    }
+}
@@ -0,0 +1,264 @@
+use crate::cost_model_tools::load_single_cost_model;
+use crate::EngineArgs;
+use polkavm::program::Instruction;
+use polkavm::{Config, ProgramBlob, Reg};
+use polkavm_common::program::asm;
+use polkavm_common::writer::ProgramBlobBuilder;
+use pvm_host::cost_model::{
+    cost_model_to_json, cost_model_weights, deserialize_cost_model_from_map,
+};
+use pvm_host::runner::{Benchmark, BenchmarkRunner, InputStrategy, Phases};
+use pvm_host::stats::linear_fit;
+use pvm_host::{module_config, Error};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug)]
+pub struct CalibrateArgs {
+    #[clap(flatten)]
+    engine: EngineArgs,
+
+    /// The cost model whose weights are kept for the instructions which aren't calibrated.
+    /// Unless `--gas-per-ns` is given the fitted model is also scaled to match it.
+    #[clap(long, value_name = "MODEL", default_value = "L1-miss")]
+    base: String,
+
+    /// How much gas to charge per nanosecond of execution time.
+    #[clap(long)]
+    gas_per_ns: Option<f64>,
+
+    /// How many times each microbenchmark runs its loop.
+    #[clap(long, default_value_t = 100_000)]
+    loop_count: u32,
+
+    /// The number of timed iterations of each microbenchmark.
+    #[clap(short, long, default_value_t = 10)]
+    iterations: u32,
+
+    /// Where to write the fitted cost model.
+    output: PathBuf,
+}
+
+/// A group of instructions which are assumed to cost roughly the same, measured through
+/// a single representative instruction.
+struct Family {
+    name: &'static str,
+    /// Builds the representative instruction; `next` is the index of the following basic block.
+    instruction: fn(next: u32) -> Instruction,
+    /// Whether the representative instruction ends a basic block.
+    is_terminator: bool,
+    /// Whether the instruction with the given name belongs to this family.
+    contains: fn(&str) -> bool,
+}
+
+/// Accesses go to the slot right below the initial stack pointer.
+const STACK_OFFSET: u32 = 0_u32.wrapping_sub(8);
+
+/// Instructions which can't be meaningfully measured in a loop, so they keep their base weights.
+const UNCALIBRATED: [&str; 5] = ["ecalli", "invalid", "memset", "sbrk", "trap"];
+
+/// The families are matched in order, so the catch-all ALU family must come last.
+const FAMILIES: [Family; 7] = [
+    Family {
+        name: "mul",
+        instruction: |_| asm::mul_64(Reg::A3, Reg::A1, Reg::A2),
+        is_terminator: false,
+        contains: |name| name.starts_with("mul_"),
+    },
+    Family {
+        name: "div",
+        instruction: |_| asm::div_unsigned_64(Reg::A3, Reg::A1, Reg::A2),
+        is_terminator: false,
+        contains: |name| name.starts_with("div_") || name.starts_with("rem_"),
+    },
+    Family {
+        name: "load",
+        instruction: |_| asm::load_indirect_u64(Reg::A3, Reg::SP, STACK_OFFSET),
+        is_terminator: false,
+        contains: |name| name.starts_with("load_") && !name.starts_with("load_imm"),
+    },
+    Family {
+        name: "store",
+        instruction: |_| asm::store_indirect_u64(Reg::A1, Reg::SP, STACK_OFFSET),
+        is_terminator: false,
+        contains: |name| name.starts_with("store_"),
+    },
+    Family {
+        name: "branch",
+        // Never taken, since A2 is never zero.
+        instruction: |next| asm::branch_eq_imm(Reg::A2, 0, next),
+        is_terminator: true,
+        contains: |name| name.starts_with("branch_"),
+    },
+    Family {
+        name: "jump",
+        instruction: asm::jump,
+        is_terminator: true,
+        contains: |name| name == "fallthrough" || name.contains("jump"),
+    },
+    Family {
+        name: "alu",
+        instruction: |_| asm::add_64(Reg::A3, Reg::A1, Reg::A2),
+        is_terminator: false,
+        contains: |name| !UNCALIBRATED.contains(&name),
+    },
+];
+
+/// Returns the name of the family to which the instruction with the given name belongs, if any.
+pub fn family_of(name: &str) -> Option<&'static str> {
+    FAMILIES
+        .iter()
+        .find(|family| (family.contains)(name))
+        .map(|family| family.name)
+}
+
+/// The number of copies of the instruction in the loop body of each microbenchmark.
+///
+/// The per-instruction cost is the slope of the execution time over these, which cancels out
+/// the overhead of the loop itself.
+const UNROLL_COUNTS: [u32; 4] = [16, 32, 48, 64];
+
+/// Builds a program which runs a loop of `unroll` copies of the family's instruction.
+fn build_program(family: &Family, unroll: u32, loop_count: u32) -> Result<ProgramBlob, String> {
+    const LOOP_BLOCK: u32 = 1;
+
+    let mut code = vec![
+        asm::load_imm(Reg::S0, loop_count),
+        asm::load_imm(Reg::A1, 0x12345678),
+        asm::load_imm(Reg::A2, 7),
+        asm::fallthrough(),
+    ];
+
+    let mut block = LOOP_BLOCK;
+    for _ in 0..unroll {
+        if family.is_terminator {
+            block += 1;
+        }
+        code.push((family.instruction)(block));
+    }
+
+    code.push(asm::add_imm_64(Reg::S0, Reg::S0, u32::MAX));
+    code.push(asm::branch_not_eq_imm(Reg::S0, 0, LOOP_BLOCK));
+    code.push(asm::jump_indirect(Reg::RA, 0));
+
+    let mut builder = ProgramBlobBuilder::new_64bit();
+    builder.set_stack_size(4096);
+    builder.add_export_by_basic_block(0, b"run");
+    builder.set_code(&code, &[]);
+
+    ProgramBlob::parse(builder.into_vec()?.into()).map_err(|error| {
+        format!(
+            "failed to parse the microbenchmark for '{}': {error}",
+            family.name
+        )
+    })
+}
+
+/// Measures how many nanoseconds a single instruction of the given family takes.
+fn calibrate_family(config: &Config, family: &Family, args: &CalibrateArgs) -> Result<f64, Error> {
+    let module_config = module_config(None, &[])?;
+
+    let mut points = Vec::new();
+    for unroll in UNROLL_COUNTS {
+        let blob = build_program(family, unroll, args.loop_count)?;
+        let benchmark = Benchmark {
+            blob: &blob,
+            export: "run",
+            data: &[],
+            gas: i64::MAX,
+            warmup: 1,
+            iterations: args.iterations,
+            reject_outliers: true,
+            perf: None,
+            reuse_instance: false,
+            input_strategy: InputStrategy::Aux,
+        };
+
+        let measurement = BenchmarkRunner::new(config, &module_config, &benchmark)?.measure()?;
+        let elapsed = Phases::median(&measurement.phases).run;
+        let executed = f64::from(unroll) * f64::from(args.loop_count);
+        points.push((executed, elapsed * 1_000_000_000.0));
+    }
+
+    match linear_fit(&points) {
+        Some((slope, _)) if slope > 0.0 => Ok(slope),
+        _ => Err(format!("failed to fit the cost of the '{}' family", family.name).into()),
+    }
+}
+
+pub fn main_calibrate(args: CalibrateArgs) -> Result<(), Error> {
+    if args.iterations == 0 {
+        return Err("the number of iterations must be at least one".into());
+    }
+
+    let base = load_single_cost_model(&args.base)?;
+    let base_weights = cost_model_weights(&base.cost_model);
+
+    let mut config =
+        Config::from_env().map_err(|error| format!("invalid configuration: {error}"))?;
+    config.set_allow_experimental(true);
+    args.engine.apply(&mut config);
+
+    let mut costs = Vec::new();
+    for family in &FAMILIES {
+        println!("Calibrating '{}'...", family.name);
+        costs.push(calibrate_family(&config, family, &args)?);
+    }
+
+    // Unless told otherwise, scale the model so that the ALU instructions cost as much as
+    // they do in the base model, which keeps the fitted model comparable with it.
+    let gas_per_ns = match args.gas_per_ns {
+        Some(gas_per_ns) => gas_per_ns,
+        None => {
+            let alu_cost = costs[FAMILIES.len() - 1];
+            f64::from(base_weights["add_64"]) / alu_cost
+        }
+    };
+
+    println!();
+    println!("  {:<10} {:>14} {:>10}", "Family", "ns/inst", "Weight");
+    for (family, cost) in FAMILIES.iter().zip(&costs) {
+        println!(
+            "  {:<10} {:>14.4} {:>10}",
+            family.name,
+            cost,
+            to_weight(*cost, gas_per_ns)
+        );
+    }
+    println!();
+    println!("Gas per nanosecond: {gas_per_ns}");
+
+    let mut weights = BTreeMap::new();
+    let mut kept = Vec::new();
+    for (&name, &base_weight) in &base_weights {
+        let family = FAMILIES.iter().position(|family| (family.contains)(name));
+        let weight = match family {
+            Some(nth) => to_weight(costs[nth], gas_per_ns),
+            None => {
+                kept.push(name);
+                base_weight
+            }
+        };
+
+        weights.insert(name.to_owned(), weight);
+    }
+
+    if !kept.is_empty() {
+        println!(
+            "Kept the weights from {} for: {}",
+            base.name,
+            kept.join(", ")
+        );
+    }
+
+    let cost_model = deserialize_cost_model_from_map(weights)?;
+    std::fs::write(&args.output, cost_model_to_json(&cost_model))
+        .map_err(|error| format!("failed to write {:?}: {error}", args.output))?;
+    println!("Cost model written to {:?}", args.output);
+
+    Ok(())
+}
+
+fn to_weight(cost: f64, gas_per_ns: f64) -> u32 {
+    (cost * gas_per_ns).round().clamp(1.0, f64::from(u32::MAX)) as u32
+}
@@ -0,0 +1,465 @@
+use crate::{BenchmarkArgs, EngineArgs, InputStrategyArgs, InstanceArgs};
+use polkavm::{BackendKind, GasMeteringKind, SandboxKind};
+use pvm_host::runner::{Benchmark, BenchmarkRunner, InputStrategy, Measurement, Phases};
+use pvm_host::{config_with_cost_model, module_config, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct CompareBackendsArgs {
+    #[clap(flatten)]
+    benchmark: BenchmarkArgs,
+
+    #[clap(flatten)]
+    strategy: InputStrategyArgs,
+
+    #[clap(flatten)]
+    instance: InstanceArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompareGasMeteringArgs {
+    #[clap(flatten)]
+    benchmark: BenchmarkArgs,
+
+    #[clap(flatten)]
+    strategy: InputStrategyArgs,
+
+    #[clap(flatten)]
+    instance: InstanceArgs,
+
+    #[clap(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompareInstantiationArgs {
+    #[clap(flatten)]
+    benchmark: BenchmarkArgs,
+
+    #[clap(flatten)]
+    strategy: InputStrategyArgs,
+
+    #[clap(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompareInputStrategiesArgs {
+    #[clap(flatten)]
+    benchmark: BenchmarkArgs,
+
+    #[clap(flatten)]
+    instance: InstanceArgs,
+
+    #[clap(flatten)]
+    engine: EngineArgs,
+
+    /// The export which reads the input through the `read_input` host call. The "host-call"
+    /// strategy is skipped if not given.
+    #[clap(long, value_name = "NAME")]
+    streaming_export: Option<String>,
+}
+
+/// Every backend and sandbox combination which we try to run under.
+const TARGETS: [(&str, BackendKind, Option<SandboxKind>); 3] = [
+    ("interpreter", BackendKind::Interpreter, None),
+    (
+        "compiler (linux)",
+        BackendKind::Compiler,
+        Some(SandboxKind::Linux),
+    ),
+    (
+        "compiler (generic)",
+        BackendKind::Compiler,
+        Some(SandboxKind::Generic),
+    ),
+];
+
+const GAS_METERING: [(&str, Option<GasMeteringKind>); 3] = [
+    ("none", None),
+    ("sync", Some(GasMeteringKind::Sync)),
+    ("async", Some(GasMeteringKind::Async)),
+];
+
+struct Row<'a> {
+    label: &'a str,
+    measurement: Measurement,
+    /// The value of the last column, relative to some baseline.
+    relative: String,
+    is_mismatch: bool,
+}
+
+fn print_table(label_header: &str, relative_header: &str, rows: &[Row]) {
+    println!(
+        "  {:<20} {:>18} {:>18} {:>14} {:>14} {:>14} {:>9}",
+        label_header, "Result", "Gas used", "Median (s)", "Mean (s)", "Stddev (s)", relative_header
+    );
+
+    for row in rows {
+        let measurement = &row.measurement;
+        let (median, mean, stddev) = match measurement.summary {
+            Some(ref summary) => (
+                format!("{:.9}", summary.median),
+                format!("{:.9}", summary.mean),
+                format!("{:.9}", summary.stddev),
+            ),
+            None => Default::default(),
+        };
+
+        println!(
+            "  {:<20} {:>18} {:>18} {:>14} {:>14} {:>14} {:>9}{}",
+            row.label,
+            format!("0x{:x}", measurement.result),
+            measurement.gas_used,
+            median,
+            mean,
+            stddev,
+            row.relative,
+            if row.is_mismatch { "  MISMATCH" } else { "" }
+        );
+    }
+}
+
+fn median_of(measurement: &Measurement) -> Option<f64> {
+    measurement.summary.as_ref().map(|summary| summary.median)
+}
+
+pub fn main_compare_backends(args: CompareBackendsArgs) -> Result<(), Error> {
+    let inputs = args.benchmark.load()?;
+    let benchmark = Benchmark {
+        reuse_instance: args.instance.reuse_instance,
+        input_strategy: args.strategy.input_strategy.into(),
+        ..args.benchmark.benchmark(&inputs)
+    };
+    let module_config = module_config(Some(GasMeteringKind::Sync), &inputs.data)?;
+
+    let mut mismatch_count = 0;
+    for cost_model in &inputs.cost_models {
+        println!("Using gas cost model: {}", cost_model.name);
+
+        let mut measurements: Vec<(&str, Measurement)> = Vec::new();
+        for (target, backend, sandbox) in TARGETS {
+            let mut config = config_with_cost_model(&cost_model.cost_model)?;
+            config.set_backend(Some(backend));
+            config.set_sandbox(sandbox);
+
+            if let Err(error) = polkavm::Engine::new(&config) {
+                println!("  Skipping {target}: {error}");
+                continue;
+            }
+
+            println!("  Running under {target}...");
+            let mut runner = BenchmarkRunner::new(&config, &module_config, &benchmark)?;
+            measurements.push((target, runner.measure()?));
+        }
+
+        let Some((_, reference)) = measurements.first() else {
+            return Err("no backend is available".into());
+        };
+
+        let (reference_result, reference_gas_used) = (reference.result, reference.gas_used);
+        let fastest = measurements
+            .iter()
+            .filter_map(|(_, measurement)| median_of(measurement))
+            .min_by(f64::total_cmp);
+
+        let rows: Vec<Row> = measurements
+            .into_iter()
+            .map(|(label, measurement)| {
+                let is_mismatch = measurement.result != reference_result
+                    || measurement.gas_used != reference_gas_used;
+                let relative = match (median_of(&measurement), fastest) {
+                    (Some(median), Some(fastest)) => format!("{:.2}x", median / fastest),
+                    _ => String::new(),
+                };
+
+                Row {
+                    label,
+                    measurement,
+                    relative,
+                    is_mismatch,
+                }
+            })
+            .collect();
+
+        mismatch_count += rows.iter().filter(|row| row.is_mismatch).count();
+
+        println!();
+        print_table("Backend", "Relative", &rows);
+        println!();
+    }
+
+    if mismatch_count > 0 {
+        return Err(format!(
+            "{mismatch_count} run(s) produced a different result or gas usage than the first backend"
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn main_compare_gas_metering(args: CompareGasMeteringArgs) -> Result<(), Error> {
+    let inputs = args.benchmark.load()?;
+    let benchmark = Benchmark {
+        reuse_instance: args.instance.reuse_instance,
+        input_strategy: args.strategy.input_strategy.into(),
+        ..args.benchmark.benchmark(&inputs)
+    };
+
+    let mut mismatch_count = 0;
+    for cost_model in &inputs.cost_models {
+        println!("Using gas cost model: {}", cost_model.name);
+
+        let mut config = config_with_cost_model(&cost_model.cost_model)?;
+        args.engine.apply(&mut config);
+
+        let mut measurements: Vec<(&str, Measurement)> = Vec::new();
+        for (label, gas_metering) in GAS_METERING {
+            println!("  Running with gas metering: {label}...");
+            let module_config = module_config(gas_metering, &inputs.data)?;
+            let mut runner = BenchmarkRunner::new(&config, &module_config, &benchmark)?;
+            measurements.push((label, runner.measure()?));
+        }
+
+        // Without any gas metering no gas is consumed, so only the results can be compared.
+        let (_, unmetered) = &measurements[0];
+        let (_, sync) = &measurements[1];
+        let (unmetered_result, unmetered_median) = (unmetered.result, median_of(unmetered));
+        let sync_gas_used = sync.gas_used;
+
+        let rows: Vec<Row> = measurements
+            .into_iter()
+            .map(|(label, measurement)| {
+                let is_mismatch = measurement.result != unmetered_result
+                    || (label != "none" && measurement.gas_used != sync_gas_used);
+                let relative = match (median_of(&measurement), unmetered_median) {
+                    (Some(median), Some(baseline)) => {
+                        format!("{:+.1}%", (median / baseline - 1.0) * 100.0)
+                    }
+                    _ => String::new(),
+                };
+
+                Row {
+                    label,
+                    measurement,
+                    relative,
+                    is_mismatch,
+                }
+            })
+            .collect();
+
+        mismatch_count += rows.iter().filter(|row| row.is_mismatch).count();
+        let gas_agrees = rows[1].measurement.gas_used == rows[2].measurement.gas_used;
+
+        println!();
+        print_table("Gas metering", "Overhead", &rows);
+        println!();
+        if gas_agrees {
+            println!(
+                "  Synchronous and asynchronous gas metering consumed the same amount of gas."
+            );
+        } else {
+            println!(
+                "  Synchronous and asynchronous gas metering consumed a different amount of gas!"
+            );
+        }
+        println!();
+    }
+
+    if mismatch_count > 0 {
+        return Err(format!(
+            "{mismatch_count} run(s) produced a different result or gas usage than expected"
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn main_compare_instantiation(args: CompareInstantiationArgs) -> Result<(), Error> {
+    // Only the timed iterations reuse the instance, so without them there's nothing to compare.
+    if args.benchmark.iterations == 0 {
+        return Err("the number of iterations must be at least one".into());
+    }
+
+    let inputs = args.benchmark.load()?;
+    let module_config = module_config(Some(GasMeteringKind::Sync), &inputs.data)?;
+
+    let mut mismatch_count = 0;
+    for cost_model in &inputs.cost_models {
+        println!("Using gas cost model: {}", cost_model.name);
+
+        let mut config = config_with_cost_model(&cost_model.cost_model)?;
+        args.engine.apply(&mut config);
+
+        let mut measurements: Vec<(&str, Measurement)> = Vec::new();
+        for (label, reuse_instance) in [("fresh", false), ("reused", true)] {
+            println!("  Running with a {label} instance...");
+            let benchmark = Benchmark {
+                reuse_instance,
+                input_strategy: args.strategy.input_strategy.into(),
+                ..args.benchmark.benchmark(&inputs)
+            };
+            let mut runner = BenchmarkRunner::new(&config, &module_config, &benchmark)?;
+            measurements.push((label, runner.measure()?));
+        }
+
+        let (_, fresh) = &measurements[0];
+        let (_, reused) = &measurements[1];
+        let is_mismatch = fresh.result != reused.result || fresh.gas_used != reused.gas_used;
+        if is_mismatch {
+            mismatch_count += 1;
+        }
+
+        println!();
+        println!(
+            "  {:<12} {:>18} {:>18} {:>16} {:>16} {:>16} {:>16}",
+            "Instance",
+            "Result",
+            "Gas used",
+            "Setup (s)",
+            "Write input (s)",
+            "Run (s)",
+            "Total (s)"
+        );
+        for (label, measurement) in &measurements {
+            // The setup is either the instantiation or the reset, depending on the mode.
+            let phases = Phases::median(&measurement.phases);
+            println!(
+                "  {:<12} {:>18} {:>18} {:>16.9} {:>16.9} {:>16.9} {:>16.9}{}",
+                label,
+                format!("0x{:x}", measurement.result),
+                measurement.gas_used,
+                phases.instantiate + phases.reset,
+                phases.write_input,
+                phases.run,
+                phases.execution(),
+                if is_mismatch { "  MISMATCH" } else { "" }
+            );
+        }
+
+        let fresh_setup = Phases::median(&fresh.phases).instantiate;
+        let reset = Phases::median(&reused.phases).reset;
+        println!();
+        if fresh_setup > 0.0 && reset > 0.0 {
+            println!(
+                "  Resetting an instance takes {:.2}x as long as instantiating a fresh one.",
+                reset / fresh_setup
+            );
+        }
+        println!();
+    }
+
+    if mismatch_count > 0 {
+        return Err(format!(
+            "{mismatch_count} cost model(s) produced a different result or gas usage with a reused instance"
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+pub fn main_compare_input_strategies(args: CompareInputStrategiesArgs) -> Result<(), Error> {
+    if args.benchmark.iterations == 0 {
+        return Err("the number of iterations must be at least one".into());
+    }
+
+    let inputs = args.benchmark.load()?;
+    let module_config = module_config(Some(GasMeteringKind::Sync), &inputs.data)?;
+
+    let mut strategies = vec![
+        ("aux", InputStrategy::Aux),
+        ("heap", InputStrategy::Heap),
+        ("read-only", InputStrategy::ReadOnly),
+    ];
+    if args.streaming_export.is_some() {
+        strategies.push(("host-call", InputStrategy::HostCall));
+    }
+
+    let mut mismatch_count = 0;
+    for cost_model in &inputs.cost_models {
+        println!("Using gas cost model: {}", cost_model.name);
+
+        let mut config = config_with_cost_model(&cost_model.cost_model)?;
+        args.engine.apply(&mut config);
+
+        // A strategy can fail on its own, e.g. when the input doesn't fit into the aux data,
+        // so the others still run.
+        let mut measurements: Vec<(&str, Measurement)> = Vec::new();
+        for &(label, input_strategy) in &strategies {
+            println!("  Running with the input passed through: {label}...");
+            let export = match input_strategy {
+                InputStrategy::HostCall => args.streaming_export.as_deref().unwrap(),
+                _ => &args.benchmark.inputs.export,
+            };
+            let benchmark = Benchmark {
+                export,
+                reuse_instance: args.instance.reuse_instance,
+                input_strategy,
+                ..args.benchmark.benchmark(&inputs)
+            };
+
+            let measurement = BenchmarkRunner::new(&config, &module_config, &benchmark)
+                .and_then(|mut runner| runner.measure());
+            match measurement {
+                Ok(measurement) => measurements.push((label, measurement)),
+                Err(error) => println!("  FAILED: {error}"),
+            }
+        }
+
+        let Some((_, reference)) = measurements.first() else {
+            return Err("the program failed with every input strategy".into());
+        };
+        let reference_result = reference.result;
+
+        println!();
+        println!(
+            "  {:<12} {:>18} {:>18} {:>16} {:>16} {:>16} {:>16}",
+            "Strategy",
+            "Result",
+            "Gas used",
+            "Write input (s)",
+            "Host calls (s)",
+            "Run (s)",
+            "Total (s)"
+        );
+        for (label, measurement) in &measurements {
+            // The gas differs between the exports, so only the results are compared.
+            let is_mismatch = measurement.result != reference_result;
+            if is_mismatch {
+                mismatch_count += 1;
+            }
+
+            // The host calls are only recorded for the initial run, so that's what's shown
+            // for them; they're also included in the run time.
+            let phases = Phases::median(&measurement.phases);
+            let host_calls = measurement
+                .host_calls
+                .get("read_input")
+                .map(|stats| stats.elapsed)
+                .unwrap_or_default();
+            println!(
+                "  {:<12} {:>18} {:>18} {:>16.9} {:>16.9} {:>16.9} {:>16.9}{}",
+                label,
+                format!("0x{:x}", measurement.result),
+                measurement.gas_used,
+                phases.write_input,
+                host_calls,
+                phases.run,
+                phases.execution(),
+                if is_mismatch { "  MISMATCH" } else { "" }
+            );
+        }
+        println!();
+    }
+
+    if mismatch_count > 0 {
+        return Err(format!(
+            "{mismatch_count} run(s) produced a different result than with the first input strategy"
+        )
+        .into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,464 @@
+use polkavm::CostModel;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+macro_rules! weights_io {
+    (generate_code_impl
+        $output:ident $model:ident
+        $($inst:ident,)+
+    ) => {
+        let CostModel { $($inst,)+ } = $model;
+        $({
+            writeln!(&mut $output, "    {}: {},", stringify!($inst), $inst).unwrap();
+        })+
+    };
+
+    (generate_json_impl
+        $output:ident $model:ident
+        $($inst:ident,)+
+    ) => {
+        let CostModel { $($inst,)+ } = $model;
+        $({
+            writeln!(&mut $output, "    \"{}\": {},", stringify!($inst), $inst).unwrap();
+        })+
+    };
+
+    (generate_json $output:ident $model:ident) => {
+        weights_io! {
+            call generate_json_impl $output $model
+        }
+    };
+
+    (generate_code $output:ident $model:ident) => {
+        weights_io! {
+            call generate_code_impl $output $model
+        }
+    };
+
+    (model_from_map_impl $map:ident $model:ident $missing:ident $($inst:ident,)+) => {
+        $(
+            match $map.remove(stringify!($inst)) {
+                Some(cost) => $model.$inst = cost,
+                None => $missing.push(stringify!($inst)),
+            }
+        )+
+    };
+
+    (model_from_map $map:ident $model:ident $missing:ident) => {
+        weights_io! {
+            call model_from_map_impl $map $model $missing
+        }
+    };
+
+    (model_to_map_impl $map:ident $model:ident $($inst:ident,)+) => {
+        $(
+            $map.insert(stringify!($inst), $model.$inst);
+        )+
+    };
+
+    (model_to_map $map:ident $model:ident) => {
+        weights_io! {
+            call model_to_map_impl $map $model
+        }
+    };
+
+    (call
+        $($args:ident)+
+    ) => {
+        weights_io! {
+            $($args)+
+
+            add_32,
+            add_64,
+            add_imm_32,
+            add_imm_64,
+            and,
+            and_imm,
+            and_inverted,
+            branch_eq,
+            branch_eq_imm,
+            branch_greater_or_equal_signed,
+            branch_greater_or_equal_signed_imm,
+            branch_greater_or_equal_unsigned,
+            branch_greater_or_equal_unsigned_imm,
+            branch_greater_signed_imm,
+            branch_greater_unsigned_imm,
+            branch_less_or_equal_signed_imm,
+            branch_less_or_equal_unsigned_imm,
+            branch_less_signed,
+            branch_less_signed_imm,
+            branch_less_unsigned,
+            branch_less_unsigned_imm,
+            branch_not_eq,
+            branch_not_eq_imm,
+            cmov_if_not_zero,
+            cmov_if_not_zero_imm,
+            cmov_if_zero,
+            cmov_if_zero_imm,
+            count_leading_zero_bits_32,
+            count_leading_zero_bits_64,
+            count_set_bits_32,
+            count_set_bits_64,
+            count_trailing_zero_bits_32,
+            count_trailing_zero_bits_64,
+            div_signed_32,
+            div_signed_64,
+            div_unsigned_32,
+            div_unsigned_64,
+            ecalli,
+            fallthrough,
+            invalid,
+            jump,
+            jump_indirect,
+            load_i16,
+            load_i32,
+            load_i8,
+            load_imm,
+            load_imm64,
+            load_imm_and_jump,
+            load_imm_and_jump_indirect,
+            load_indirect_i16,
+            load_indirect_i32,
+            load_indirect_i8,
+            load_indirect_u16,
+            load_indirect_u32,
+            load_indirect_u64,
+            load_indirect_u8,
+            load_u16,
+            load_u32,
+            load_u64,
+            load_u8,
+            maximum,
+            maximum_unsigned,
+            memset,
+            minimum,
+            minimum_unsigned,
+            move_reg,
+            mul_32,
+            mul_64,
+            mul_imm_32,
+            mul_imm_64,
+            mul_upper_signed_signed,
+            mul_upper_signed_unsigned,
+            mul_upper_unsigned_unsigned,
+            negate_and_add_imm_32,
+            negate_and_add_imm_64,
+            or,
+            or_imm,
+            or_inverted,
+            rem_signed_32,
+            rem_signed_64,
+            rem_unsigned_32,
+            rem_unsigned_64,
+            reverse_byte,
+            rotate_left_32,
+            rotate_left_64,
+            rotate_right_32,
+            rotate_right_64,
+            rotate_right_imm_32,
+            rotate_right_imm_64,
+            rotate_right_imm_alt_32,
+            rotate_right_imm_alt_64,
+            sbrk,
+            set_greater_than_signed_imm,
+            set_greater_than_unsigned_imm,
+            set_less_than_signed,
+            set_less_than_signed_imm,
+            set_less_than_unsigned,
+            set_less_than_unsigned_imm,
+            shift_arithmetic_right_32,
+            shift_arithmetic_right_64,
+            shift_arithmetic_right_imm_32,
+            shift_arithmetic_right_imm_64,
+            shift_arithmetic_right_imm_alt_32,
+            shift_arithmetic_right_imm_alt_64,
+            shift_logical_left_32,
+            shift_logical_left_64,
+            shift_logical_left_imm_32,
+            shift_logical_left_imm_64,
+            shift_logical_left_imm_alt_32,
+            shift_logical_left_imm_alt_64,
+            shift_logical_right_32,
+            shift_logical_right_64,
+            shift_logical_right_imm_32,
+            shift_logical_right_imm_64,
+            shift_logical_right_imm_alt_32,
+            shift_logical_right_imm_alt_64,
+            sign_extend_16,
+            sign_extend_8,
+            store_imm_indirect_u16,
+            store_imm_indirect_u32,
+            store_imm_indirect_u64,
+            store_imm_indirect_u8,
+            store_imm_u16,
+            store_imm_u32,
+            store_imm_u64,
+            store_imm_u8,
+            store_indirect_u16,
+            store_indirect_u32,
+            store_indirect_u64,
+            store_indirect_u8,
+            store_u16,
+            store_u32,
+            store_u64,
+            store_u8,
+            sub_32,
+            sub_64,
+            trap,
+            xnor,
+            xor,
+            xor_imm,
+            zero_extend_16,
+        }
+    };
+}
+
+/// Returns the weight of every instruction in the cost model, keyed by the instruction's name.
+pub fn cost_model_weights(cost_model: &CostModel) -> BTreeMap<&'static str, u32> {
+    let mut map = BTreeMap::new();
+    weights_io! {
+        model_to_map map cost_model
+    }
+
+    map
+}
+
+/// Serializes the cost model as a Rust `CostModel` literal.
+pub fn cost_model_to_code(cost_model: &CostModel) -> String {
+    let mut output = String::new();
+    writeln!(&mut output, "CostModel {{").unwrap();
+    weights_io! {
+        generate_code output cost_model
+    }
+    writeln!(&mut output, "}}").unwrap();
+    output
+}
+
+/// Serializes the cost model as JSON, with the keys sorted and one key per line.
+pub fn cost_model_to_json(cost_model: &CostModel) -> String {
+    let mut output = String::new();
+    writeln!(&mut output, "{{").unwrap();
+    weights_io! {
+        generate_json output cost_model
+    }
+
+    // JSON doesn't allow a trailing comma after the last key.
+    output.truncate(output.trim_end_matches([',', '\n']).len());
+    writeln!(&mut output, "\n}}").unwrap();
+    output
+}
+
+pub fn deserialize_cost_model_from_map(
+    mut map: BTreeMap<String, u32>,
+) -> Result<CostModel, String> {
+    let mut cost_model = CostModel::naive();
+    let mut missing_keys: Vec<&str> = Vec::new();
+    weights_io! {
+        model_from_map map cost_model missing_keys
+    }
+
+    let mut errors = Vec::new();
+    if !missing_keys.is_empty() {
+        let missing_keys: Vec<_> = missing_keys
+            .into_iter()
+            .map(|key| format!("'{}'", key))
+            .collect();
+        let missing_keys = missing_keys.join(", ");
+        errors.push(format!("missing keys: {missing_keys}"));
+    }
+
+    if !map.is_empty() {
+        let extra_keys: Vec<_> = map.into_keys().map(|key| format!("'{}'", key)).collect();
+        let extra_keys = extra_keys.join(", ");
+        errors.push(format!("extra keys: {extra_keys}"));
+    }
+
+    if !errors.is_empty() {
+        return Err(format!(
+            "failed to deserialize cost model: {}",
+            errors.join("; ")
+        ));
+    }
+
+    Ok(cost_model)
+}
+
+/// The cost models embedded into the binary, selectable by name.
+pub const EMBEDDED_COST_MODELS: &[(&str, &[u8])] = &[
+    ("L1-miss", include_bytes!("../model-l1-miss.json")),
+    ("L2-miss", include_bytes!("../model-l2-miss.json")),
+    ("L3-miss", include_bytes!("../model-l3-miss.json")),
+];
+
+pub struct NamedCostModel {
+    pub name: String,
+    pub cost_model: Arc<CostModel>,
+}
+
+fn parse_cost_model(cost_blob: &[u8]) -> Result<CostModel, String> {
+    let blob =
+        core::str::from_utf8(cost_blob).map_err(|error| format!("not valid UTF-8: {error}"))?;
+    let map: BTreeMap<String, u32> =
+        serde_json::from_str(blob).map_err(|error| format!("not a valid JSON map: {error}"))?;
+    deserialize_cost_model_from_map(map)
+}
+
+fn load_cost_model_from_file(path: &Path) -> Result<NamedCostModel, String> {
+    let cost_blob =
+        std::fs::read(path).map_err(|error| format!("failed to read {path:?}: {error}"))?;
+    let cost_model = parse_cost_model(&cost_blob)
+        .map_err(|error| format!("failed to parse the cost model from {path:?}: {error}"))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+
+    Ok(NamedCostModel {
+        name,
+        cost_model: Arc::new(cost_model),
+    })
+}
+
+/// Loads cost models given either the name of one of the embedded models, a path to a JSON file,
+/// or a path to a directory of JSON files.
+///
+/// Every file is loaded independently, so a single broken file doesn't prevent the rest from loading.
+pub fn load_cost_models(spec: &str) -> Vec<Result<NamedCostModel, String>> {
+    if let Some((name, cost_blob)) = EMBEDDED_COST_MODELS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(spec))
+    {
+        let cost_model = parse_cost_model(cost_blob)
+            .map(|cost_model| NamedCostModel {
+                name: name.to_string(),
+                cost_model: Arc::new(cost_model),
+            })
+            .map_err(|error| format!("failed to parse the cost model for {name}: {error}"));
+        return vec![cost_model];
+    }
+
+    let path = Path::new(spec);
+    if path.is_dir() {
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(error) => return vec![Err(format!("failed to read {path:?}: {error}"))],
+        };
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => paths.push(entry.path()),
+                Err(error) => return vec![Err(format!("failed to read {path:?}: {error}"))],
+            }
+        }
+
+        paths.retain(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+        });
+        if paths.is_empty() {
+            return vec![Err(format!("no cost models found in {path:?}"))];
+        }
+
+        paths.sort();
+        return paths
+            .iter()
+            .map(|path| load_cost_model_from_file(path))
+            .collect();
+    }
+
+    if !path.exists() {
+        let names: Vec<_> = EMBEDDED_COST_MODELS.iter().map(|(name, _)| *name).collect();
+        return vec![Err(format!(
+            "unknown cost model '{spec}': not a file or a directory, and not one of: {}",
+            names.join(", ")
+        ))];
+    }
+
+    vec![load_cost_model_from_file(path)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedded(name: &str) -> NamedCostModel {
+        load_cost_models(name).pop().unwrap().unwrap()
+    }
+
+    fn weights_as_map(cost_model: &CostModel) -> BTreeMap<String, u32> {
+        cost_model_weights(cost_model)
+            .into_iter()
+            .map(|(name, weight)| (name.to_owned(), weight))
+            .collect()
+    }
+
+    /// Writes the weights to a JSON file of their own and loads it back.
+    fn reload(file_name: &str, weights: &BTreeMap<String, u32>) -> Result<NamedCostModel, String> {
+        let directory =
+            std::env::temp_dir().join(format!("pvm-host-test-{}-{file_name}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(file_name);
+        std::fs::write(&path, serde_json::to_string(weights).unwrap()).unwrap();
+
+        let mut loaded = load_cost_models(path.to_str().unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(loaded.len(), 1);
+        loaded.pop().unwrap()
+    }
+
+    #[test]
+    fn embedded_models_round_trip_through_json() {
+        for (name, _) in EMBEDDED_COST_MODELS {
+            let original = embedded(name);
+            let json = cost_model_to_json(&original.cost_model);
+            let weights: BTreeMap<String, u32> = serde_json::from_str(&json).unwrap();
+
+            let reloaded = reload(&format!("{name}.json"), &weights).unwrap();
+            assert_eq!(reloaded.name, *name);
+            assert_eq!(
+                cost_model_weights(&reloaded.cost_model),
+                cost_model_weights(&original.cost_model)
+            );
+            assert_eq!(cost_model_to_json(&reloaded.cost_model), json);
+        }
+    }
+
+    #[test]
+    fn missing_keys_are_rejected() {
+        let mut weights = weights_as_map(&embedded("L1-miss").cost_model);
+        weights.remove("add_64");
+        weights.remove("xor");
+
+        let error = reload("missing.json", &weights).err().unwrap();
+        assert!(error.contains("missing keys: 'add_64', 'xor'"), "{error}");
+        assert!(!error.contains("extra keys"), "{error}");
+    }
+
+    #[test]
+    fn extra_keys_are_rejected() {
+        let mut weights = weights_as_map(&embedded("L1-miss").cost_model);
+        weights.insert("frobnicate".into(), 1);
+
+        let error = reload("extra.json", &weights).err().unwrap();
+        assert!(error.contains("extra keys: 'frobnicate'"), "{error}");
+        assert!(!error.contains("missing keys"), "{error}");
+    }
+
+    #[test]
+    fn missing_and_extra_keys_are_reported_together() {
+        let mut weights = weights_as_map(&embedded("L1-miss").cost_model);
+        weights.remove("add_64");
+        weights.insert("frobnicate".into(), 1);
+
+        let error = deserialize_cost_model_from_map(weights).err().unwrap();
+        assert_eq!(
+            error,
+            "failed to deserialize cost model: missing keys: 'add_64'; extra keys: 'frobnicate'"
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use crate::calibrate::family_of;
+use polkavm::CostModel;
+use pvm_host::cost_model::{
+    cost_model_to_code, cost_model_to_json, cost_model_weights, deserialize_cost_model_from_map,
+    load_cost_models, NamedCostModel,
+};
+use pvm_host::Error;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum CostModelFormat {
+    /// Canonical JSON, with the keys sorted and one key per line.
+    Json,
+    /// A Rust `CostModel { ... }` literal.
+    Rust,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExportCostModelArgs {
+    /// The format to export the cost model in.
+    #[clap(short, long, value_enum, default_value_t = CostModelFormat::Json)]
+    format: CostModelFormat,
+
+    /// Write the cost model to this file instead of to the standard output.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// The cost model to export; either the name of an embedded model (L1-miss, L2-miss, L3-miss)
+    /// or a path to a JSON file.
+    model: String,
+}
+
+/// Loads exactly one cost model, given the name of an embedded model or a path to a JSON file.
+pub fn load_single_cost_model(spec: &str) -> Result<NamedCostModel, String> {
+    let mut cost_models = load_cost_models(spec);
+    if cost_models.len() != 1 {
+        return Err(format!(
+            "expected a single cost model in '{spec}', found {}",
+            cost_models.len()
+        ));
+    }
+
+    cost_models.remove(0)
+}
+
+/// Writes the output either to the given file, or to the standard output if there's none.
+pub fn write_output(path: Option<&Path>, contents: &str) -> Result<(), String> {
+    match path {
+        Some(path) => std::fs::write(path, contents)
+            .map_err(|error| format!("failed to write {path:?}: {error}")),
+        None => {
+            print!("{contents}");
+            Ok(())
+        }
+    }
+}
+
+pub fn main_export_cost_model(args: ExportCostModelArgs) -> Result<(), Error> {
+    let cost_model = load_single_cost_model(&args.model)?;
+    let contents = match args.format {
+        CostModelFormat::Json => cost_model_to_json(&cost_model.cost_model),
+        CostModelFormat::Rust => cost_model_to_code(&cost_model.cost_model),
+    };
+
+    write_output(args.output.as_deref(), &contents)?;
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DiffCostModelsArgs {
+    /// Also list the instructions whose weight didn't change.
+    #[clap(short, long)]
+    all: bool,
+
+    /// The cost model to compare against.
+    old: String,
+
+    /// The cost model to compare.
+    new: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DeriveCostModelArgs {
+    /// Interpolate between the base model and this model.
+    #[clap(long, value_name = "MODEL", requires = "weight")]
+    interpolate: Option<String>,
+
+    /// How far to interpolate towards the other model; 0 is the base model, 1 is the other one.
+    #[clap(long, requires = "interpolate")]
+    weight: Option<f64>,
+
+    /// Multiply every weight by this factor.
+    #[clap(long, default_value_t = 1.0)]
+    scale: f64,
+
+    /// Override the weights of some instructions, in the form of "<NAME>=<WEIGHT>", where the name
+    /// is either the name of an instruction, a prefix of names ending with '*' (e.g. "branch_*"),
+    /// or the name of an instruction family used by `calibrate` (e.g. "div").
+    /// Applied after the interpolation and scaling. Can be specified multiple times.
+    #[clap(long = "set", value_name = "NAME=WEIGHT")]
+    overrides: Vec<String>,
+
+    /// Write the derived model to this file instead of to the standard output.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// The cost model to start from.
+    base: String,
+}
+
+pub fn main_diff_cost_models(args: DiffCostModelsArgs) -> Result<(), Error> {
+    let old = load_single_cost_model(&args.old)?;
+    let new = load_single_cost_model(&args.new)?;
+    let old_weights = cost_model_weights(&old.cost_model);
+    let new_weights = cost_model_weights(&new.cost_model);
+
+    println!(
+        "  {:<40} {:>10} {:>10} {:>11} {:>8}",
+        "Instruction", old.name, new.name, "Delta", "Ratio"
+    );
+
+    let mut changed = 0;
+    for (name, &old_weight) in &old_weights {
+        let new_weight = new_weights[name];
+        if old_weight != new_weight {
+            changed += 1;
+        } else if !args.all {
+            continue;
+        }
+
+        let ratio = if old_weight == 0 {
+            String::from("-")
+        } else {
+            format!("{:.3}", f64::from(new_weight) / f64::from(old_weight))
+        };
+
+        println!(
+            "  {:<40} {:>10} {:>10} {:>+11} {:>8}",
+            name,
+            old_weight,
+            new_weight,
+            i64::from(new_weight) - i64::from(old_weight),
+            ratio
+        );
+    }
+
+    println!();
+    println!(
+        "{changed} of {} instruction weights differ",
+        old_weights.len()
+    );
+
+    Ok(())
+}
+
+/// Parses an override in the form of "<NAME>=<WEIGHT>" and returns the matching instructions.
+fn parse_override<'a>(
+    spec: &str,
+    names: impl Iterator<Item = &'a str>,
+) -> Result<(Vec<&'a str>, u32), String> {
+    let Some((pattern, weight)) = spec.split_once('=') else {
+        return Err(format!(
+            "invalid override '{spec}': expected '<NAME>=<WEIGHT>'"
+        ));
+    };
+
+    let weight: u32 = weight
+        .trim()
+        .parse()
+        .map_err(|error| format!("invalid weight in override '{spec}': {error}"))?;
+
+    let pattern = pattern.trim();
+    let matches: Vec<&str> = names
+        .filter(|name| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => *name == pattern || family_of(name) == Some(pattern),
+        })
+        .collect();
+
+    if matches.is_empty() {
+        return Err(format!("override '{spec}' doesn't match any instruction"));
+    }
+
+    Ok((matches, weight))
+}
+
+/// Derives the weights of a new cost model from the base one; see [`DeriveCostModelArgs`].
+fn derive_cost_model(
+    base: &CostModel,
+    interpolate: Option<(&CostModel, f64)>,
+    scale: f64,
+    overrides: &[String],
+) -> Result<CostModel, String> {
+    let mut weights: BTreeMap<&str, f64> = cost_model_weights(base)
+        .into_iter()
+        .map(|(name, weight)| (name, f64::from(weight)))
+        .collect();
+
+    if let Some((other, factor)) = interpolate {
+        let other_weights = cost_model_weights(other);
+        for (name, weight) in &mut weights {
+            *weight += (f64::from(other_weights[name]) - *weight) * factor;
+        }
+    }
+
+    for weight in weights.values_mut() {
+        *weight *= scale;
+    }
+
+    let names: Vec<&str> = weights.keys().copied().collect();
+    for spec in overrides {
+        let (matches, weight) = parse_override(spec, names.iter().copied())?;
+        for name in matches {
+            weights.insert(name, f64::from(weight));
+        }
+    }
+
+    let weights: BTreeMap<String, u32> = weights
+        .into_iter()
+        .map(|(name, weight)| {
+            let weight = weight.round().clamp(0.0, f64::from(u32::MAX)) as u32;
+            (name.to_owned(), weight)
+        })
+        .collect();
+
+    deserialize_cost_model_from_map(weights)
+}
+
+pub fn main_derive_cost_model(args: DeriveCostModelArgs) -> Result<(), Error> {
+    if !args.scale.is_finite() || args.scale < 0.0 {
+        return Err(format!("invalid scale factor: {}", args.scale).into());
+    }
+
+    if let Some(weight) = args.weight {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(format!(
+                "invalid interpolation weight: {weight}; expected a value between 0 and 1"
+            )
+            .into());
+        }
+    }
+
+    let base = load_single_cost_model(&args.base)?;
+    let other = match (&args.interpolate, args.weight) {
+        (Some(other), Some(factor)) => Some((load_single_cost_model(other)?, factor)),
+        _ => None,
+    };
+
+    let interpolate = other
+        .as_ref()
+        .map(|(other, factor)| (&*other.cost_model, *factor));
+    let cost_model = derive_cost_model(&base.cost_model, interpolate, args.scale, &args.overrides)?;
+    write_output(args.output.as_deref(), &cost_model_to_json(&cost_model))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [&str; 5] = [
+        "add_64",
+        "branch_eq",
+        "branch_ne",
+        "div_unsigned_64",
+        "rem_signed_64",
+    ];
+
+    fn embedded(name: &str) -> NamedCostModel {
+        load_single_cost_model(name).unwrap()
+    }
+
+    #[test]
+    fn parse_override_matches_names_prefixes_and_families() {
+        let parse = |spec| parse_override(spec, NAMES.iter().copied());
+
+        assert_eq!(parse("add_64=5"), Ok((vec!["add_64"], 5)));
+        assert_eq!(parse(" add_64 = 5 "), Ok((vec!["add_64"], 5)));
+        assert_eq!(parse("branch_*=2"), Ok((vec!["branch_eq", "branch_ne"], 2)));
+        assert_eq!(
+            parse("div=40"),
+            Ok((vec!["div_unsigned_64", "rem_signed_64"], 40))
+        );
+    }
+
+    #[test]
+    fn parse_override_rejects_unknown_names() {
+        let error = parse_override("frobnicate=1", NAMES.iter().copied()).unwrap_err();
+        assert_eq!(
+            error,
+            "override 'frobnicate=1' doesn't match any instruction"
+        );
+
+        let error = parse_override("add_64", NAMES.iter().copied()).unwrap_err();
+        assert_eq!(
+            error,
+            "invalid override 'add_64': expected '<NAME>=<WEIGHT>'"
+        );
+    }
+
+    #[test]
+    fn parse_override_rejects_bad_weights() {
+        for spec in [
+            "add_64=",
+            "add_64=-1",
+            "add_64=1.5",
+            "add_64=lots",
+            "add_64=4294967296",
+        ] {
+            let error = parse_override(spec, NAMES.iter().copied()).unwrap_err();
+            assert!(
+                error.starts_with(&format!("invalid weight in override '{spec}'")),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn derive_interpolates_between_models() {
+        let base = embedded("L1-miss");
+        let other = embedded("L3-miss");
+        let base_weights = cost_model_weights(&base.cost_model);
+        let other_weights = cost_model_weights(&other.cost_model);
+
+        let derive = |factor| {
+            let cost_model = derive_cost_model(
+                &base.cost_model,
+                Some((&other.cost_model, factor)),
+                1.0,
+                &[],
+            )
+            .unwrap();
+            cost_model_weights(&cost_model)
+        };
+
+        assert_eq!(derive(0.0), base_weights);
+        assert_eq!(derive(1.0), other_weights);
+
+        let halfway = derive(0.5);
+        for (name, weight) in halfway {
+            let expected = (f64::from(base_weights[name]) + f64::from(other_weights[name])) / 2.0;
+            assert_eq!(weight, expected.round() as u32, "{name}");
+        }
+    }
+
+    #[test]
+    fn derive_scales_before_applying_the_overrides() {
+        let base = embedded("L1-miss");
+        let base_weights = cost_model_weights(&base.cost_model);
+
+        let overrides = [String::from("add_64=7")];
+        let cost_model = derive_cost_model(&base.cost_model, None, 2.0, &overrides).unwrap();
+        for (name, weight) in cost_model_weights(&cost_model) {
+            let expected = if name == "add_64" {
+                7
+            } else {
+                base_weights[name] * 2
+            };
+            assert_eq!(weight, expected, "{name}");
+        }
+    }
+}
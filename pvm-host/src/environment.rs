@@ -0,0 +1,168 @@
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+/// The machine and the software a benchmark was run with.
+#[derive(Clone, Debug, Serialize)]
+pub struct Environment {
+    pub hostname: Option<String>,
+    pub cpu_model: Option<String>,
+    /// The core the benchmark was pinned to, if any.
+    pub pinned_cpu: Option<usize>,
+    /// The frequency scaling governor of the core the benchmark ran on.
+    pub governor: Option<String>,
+    /// Whether turbo boost is enabled, if it could be determined.
+    pub turbo: Option<bool>,
+    pub kernel: Option<String>,
+    pub polkavm_version: String,
+    pub rustc_version: String,
+    /// The commit the workspace was at when the benchmark was run.
+    pub git_commit: Option<String>,
+    /// Whether the workspace had uncommitted changes.
+    pub git_dirty: Option<bool>,
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    Some(contents.trim().to_owned())
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        matches!(key.trim(), "model name" | "Model" | "uarch").then(|| value.trim().to_owned())
+    })
+}
+
+fn turbo() -> Option<bool> {
+    if let Some(no_turbo) = read_trimmed("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+        return Some(no_turbo == "0");
+    }
+
+    read_trimmed("/sys/devices/system/cpu/cpufreq/boost").map(|boost| boost == "1")
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
+
+impl Environment {
+    /// Captures the environment, as seen from the given core.
+    pub fn capture(pinned_cpu: Option<usize>) -> Self {
+        let cpu = pinned_cpu.unwrap_or(0);
+        Environment {
+            hostname: read_trimmed("/proc/sys/kernel/hostname"),
+            cpu_model: cpu_model(),
+            pinned_cpu,
+            governor: read_trimmed(format!(
+                "/sys/devices/system/cpu/cpu{cpu}/cpufreq/scaling_governor"
+            )),
+            turbo: turbo(),
+            kernel: read_trimmed("/proc/sys/kernel/osrelease"),
+            polkavm_version: env!("PVM_HOST_POLKAVM_VERSION").to_owned(),
+            rustc_version: env!("PVM_HOST_RUSTC_VERSION").to_owned(),
+            git_commit: git(&["rev-parse", "HEAD"]),
+            git_dirty: git(&["status", "--porcelain", "--untracked-files=no"])
+                .map(|status| !status.is_empty()),
+        }
+    }
+
+    /// Returns the reasons why the timings measured in this environment might be unreliable.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(ref governor) = self.governor {
+            if governor != "performance" {
+                warnings.push(format!(
+                    "the CPU frequency governor is '{governor}' instead of 'performance', \
+                     so the clock speed may vary between runs"
+                ));
+            }
+        }
+
+        if self.turbo == Some(true) {
+            warnings.push(String::from(
+                "turbo boost is enabled, so the clock speed depends on the temperature and the load",
+            ));
+        }
+
+        if self.pinned_cpu.is_none() {
+            warnings.push(String::from(
+                "not pinned to a core; use '--pin-cpu' to avoid being migrated between cores",
+            ));
+        }
+
+        if self.git_dirty == Some(true) {
+            warnings.push(String::from("the workspace has uncommitted changes"));
+        }
+
+        warnings
+    }
+
+    pub fn print(&self) {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".into());
+        println!("Environment:");
+        println!("  Host: {}", unknown(&self.hostname));
+        println!("  CPU: {}", unknown(&self.cpu_model));
+        match self.pinned_cpu {
+            Some(cpu) => println!("  Pinned to core: {cpu}"),
+            None => println!("  Pinned to core: no"),
+        }
+        println!("  Governor: {}", unknown(&self.governor));
+        match self.turbo {
+            Some(turbo) => println!("  Turbo: {}", if turbo { "enabled" } else { "disabled" }),
+            None => println!("  Turbo: unknown"),
+        }
+        println!("  Kernel: {}", unknown(&self.kernel));
+        println!("  PolkaVM: {}", self.polkavm_version);
+        println!("  Compiler: {}", self.rustc_version);
+        match (&self.git_commit, self.git_dirty) {
+            (Some(commit), Some(true)) => println!("  Commit: {commit} (dirty)"),
+            (Some(commit), _) => println!("  Commit: {commit}"),
+            (None, _) => println!("  Commit: unknown"),
+        }
+
+        for warning in self.warnings() {
+            println!("WARNING: {warning}");
+        }
+        println!();
+    }
+}
+
+/// Pins the current thread, and any processes it spawns from now on, to the given core.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpu(cpu: usize) -> Result<(), String> {
+    // SAFETY: An all-zero `cpu_set_t` is a valid empty set.
+    let mut set: libc::cpu_set_t = unsafe { core::mem::zeroed() };
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(format!("failed to pin to core {cpu}: no such core"));
+    }
+
+    // SAFETY: The core is within the bounds of the set.
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+
+    // SAFETY: The set is valid for the duration of the call.
+    let result = unsafe { libc::sched_setaffinity(0, core::mem::size_of_val(&set), &set) };
+    if result != 0 {
+        let error = std::io::Error::last_os_error();
+        return Err(format!("failed to pin to core {cpu}: {error}"));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpu(cpu: usize) -> Result<(), String> {
+    Err(format!(
+        "failed to pin to core {cpu}: only supported on Linux"
+    ))
+}
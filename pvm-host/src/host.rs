@@ -0,0 +1,180 @@
+use polkavm::{ProgramBlob, RawInstance, Reg};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// The functions the host exposes to the guest.
+///
+/// Unless the program imports them by name, each function is available under the ecalli number
+/// equal to its index in `HostFunction::ALL`. Arguments are passed in A0..A5, and the result
+/// (if any) is returned in A0.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum HostFunction {
+    /// `log(pointer, length)`: prints a message.
+    Log,
+    /// `write_output(pointer, length)`: appends the data to the output buffer.
+    WriteOutput,
+    /// `read_input(pointer, length, offset) -> length`: copies a chunk of the input into memory.
+    ReadInput,
+    /// `get_time() -> nanoseconds`: returns a deterministic, fake timestamp.
+    GetTime,
+    /// `abort(pointer, length)`: aborts the execution with a message.
+    Abort,
+}
+
+impl HostFunction {
+    pub const ALL: [HostFunction; 5] = [
+        HostFunction::Log,
+        HostFunction::WriteOutput,
+        HostFunction::ReadInput,
+        HostFunction::GetTime,
+        HostFunction::Abort,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HostFunction::Log => "log",
+            HostFunction::WriteOutput => "write_output",
+            HostFunction::ReadInput => "read_input",
+            HostFunction::GetTime => "get_time",
+            HostFunction::Abort => "abort",
+        }
+    }
+}
+
+/// How many times a host function was called and how long it took in total.
+#[derive(Copy, Clone, Default, Debug, Serialize)]
+pub struct HostCallStats {
+    pub count: u64,
+    /// The total time spent in the host function, in seconds.
+    pub elapsed: f64,
+}
+
+/// Maps ecalli numbers to host functions.
+pub struct HostFunctions {
+    by_number: BTreeMap<u32, HostFunction>,
+}
+
+impl HostFunctions {
+    pub fn new(blob: &ProgramBlob) -> Self {
+        let mut by_number: BTreeMap<u32, HostFunction> = HostFunction::ALL
+            .iter()
+            .enumerate()
+            .map(|(number, function)| (number as u32, *function))
+            .collect();
+
+        // The linker assigns ecalli numbers to named imports, so if the program imports
+        // any of our functions by name then that takes precedence over the defaults.
+        let imports = blob.imports();
+        for number in 0..imports.len() {
+            let Some(symbol) = imports.get(number) else {
+                continue;
+            };
+
+            let function = HostFunction::ALL
+                .into_iter()
+                .find(|function| function.name().as_bytes() == symbol.as_bytes());
+
+            match function {
+                Some(function) => by_number.insert(number, function),
+                None => by_number.remove(&number),
+            };
+        }
+
+        HostFunctions { by_number }
+    }
+
+    pub fn get(&self, number: u32) -> Option<HostFunction> {
+        self.by_number.get(&number).copied()
+    }
+}
+
+/// The state the host functions operate on during a single run.
+pub struct HostState<'a> {
+    pub input: &'a [u8],
+    pub output: Vec<u8>,
+    /// Whether to print the messages the guest logs.
+    pub echo_logs: bool,
+    pub fake_time: u64,
+    pub stats: BTreeMap<&'static str, HostCallStats>,
+}
+
+impl<'a> HostState<'a> {
+    pub fn new(input: &'a [u8], echo_logs: bool) -> Self {
+        HostState {
+            input,
+            output: Vec::new(),
+            echo_logs,
+            fake_time: 0,
+            stats: BTreeMap::new(),
+        }
+    }
+
+    /// Calls the given host function.
+    ///
+    /// Returns an error if the guest asked to abort, or if the arguments were invalid.
+    pub fn call(
+        &mut self,
+        function: HostFunction,
+        instance: &mut RawInstance,
+    ) -> Result<(), String> {
+        let instant = Instant::now();
+        let result = self.call_impl(function, instance);
+
+        let stats = self.stats.entry(function.name()).or_default();
+        stats.count += 1;
+        stats.elapsed += instant.elapsed().as_secs_f64();
+
+        result
+    }
+
+    fn call_impl(
+        &mut self,
+        function: HostFunction,
+        instance: &mut RawInstance,
+    ) -> Result<(), String> {
+        let read_buffer = |instance: &mut RawInstance| {
+            let pointer = instance.reg(Reg::A0) as u32;
+            let length = instance.reg(Reg::A1) as u32;
+            instance.read_memory(pointer, length).map_err(|error| {
+                format!("{}: failed to read guest memory: {error}", function.name())
+            })
+        };
+
+        match function {
+            HostFunction::Log => {
+                let message = read_buffer(instance)?;
+                if self.echo_logs {
+                    println!("guest> {}", String::from_utf8_lossy(&message));
+                }
+            }
+            HostFunction::WriteOutput => {
+                let data = read_buffer(instance)?;
+                self.output.extend_from_slice(&data);
+            }
+            HostFunction::ReadInput => {
+                let pointer = instance.reg(Reg::A0) as u32;
+                let length = instance.reg(Reg::A1) as usize;
+                let offset = instance.reg(Reg::A2) as usize;
+                let chunk = self.input.get(offset..).unwrap_or_default();
+                let chunk = &chunk[..length.min(chunk.len())];
+                instance.write_memory(pointer, chunk).map_err(|error| {
+                    format!("read_input: failed to write guest memory: {error}")
+                })?;
+                instance.set_reg(Reg::A0, chunk.len() as u64);
+            }
+            HostFunction::GetTime => {
+                // A real clock would make runs nondeterministic, so just pretend that one
+                // millisecond passes between each call.
+                self.fake_time += 1_000_000;
+                instance.set_reg(Reg::A0, self.fake_time);
+            }
+            HostFunction::Abort => {
+                let message = read_buffer(instance)?;
+                return Err(format!("aborted: {}", String::from_utf8_lossy(&message)));
+            }
+        }
+
+        Ok(())
+    }
+}
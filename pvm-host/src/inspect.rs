@@ -0,0 +1,214 @@
+use crate::analysis::{basic_blocks, opcode_name};
+use crate::trace::percent;
+use polkavm::{BackendKind, Config, Engine, Module, ProgramBlob};
+use pvm_host::{module_config, Error};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug)]
+pub struct InspectArgs {
+    /// The input the program would be run with; only its size matters, as it determines
+    /// the size of the auxiliary data region.
+    #[clap(long)]
+    input: Option<PathBuf>,
+
+    /// Write the report to this file as JSON.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// The program blob to inspect.
+    program: PathBuf,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub offset: u32,
+}
+
+/// Where each region of the program's memory is placed once it's instantiated.
+#[derive(Clone, Debug, Serialize)]
+pub struct MemoryLayout {
+    pub page_size: u32,
+    pub ro_data_address: u32,
+    pub ro_data_size: u32,
+    pub rw_data_address: u32,
+    pub rw_data_size: u32,
+    pub heap_base: u32,
+    pub max_heap_size: u32,
+    pub stack_address_low: u32,
+    pub stack_address_high: u32,
+    pub stack_size: u32,
+    pub aux_data_address: u32,
+    pub aux_data_size: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Inspection {
+    pub program: String,
+    pub blob_size: usize,
+    pub is_64_bit: bool,
+    pub code_size: usize,
+    /// The size of the read-only data stored in the blob.
+    pub ro_data_size: usize,
+    /// The size of the read-only data region, including the part which is zero-initialized.
+    pub ro_data_region_size: u32,
+    /// The size of the read-write data stored in the blob.
+    pub rw_data_size: usize,
+    /// The size of the read-write data region, including the part which is zero-initialized.
+    pub rw_data_region_size: u32,
+    pub stack_size: u32,
+    pub exports: Vec<Symbol>,
+    pub imports: Vec<String>,
+    pub instructions: usize,
+    pub basic_blocks: usize,
+    /// The number of times each opcode appears in the code.
+    pub opcodes: BTreeMap<String, usize>,
+    pub memory_map: MemoryLayout,
+}
+
+pub fn main_inspect(args: InspectArgs) -> Result<(), Error> {
+    let raw_blob = std::fs::read(&args.program)
+        .map_err(|error| format!("failed to read {:?}: {error}", args.program))?;
+    let blob = ProgramBlob::parse(raw_blob[..].into())
+        .map_err(|error| format!("failed to parse {:?}: {error}", args.program))?;
+    let data = match args.input {
+        Some(ref path) => {
+            std::fs::read(path).map_err(|error| format!("failed to read {path:?}: {error}"))?
+        }
+        None => Vec::new(),
+    };
+
+    // The memory map is only available once the module is loaded, and the interpreter
+    // is the cheapest way to do that.
+    let mut config =
+        Config::from_env().map_err(|error| format!("invalid configuration: {error}"))?;
+    config.set_backend(Some(BackendKind::Interpreter));
+    let engine =
+        Engine::new(&config).map_err(|error| format!("failed to create the engine: {error}"))?;
+    let module = Module::from_blob(&engine, &module_config(None, &data)?, blob.clone())
+        .map_err(|error| format!("failed to load the module: {error}"))?;
+    let memory_map = module.memory_map();
+
+    let mut opcodes: BTreeMap<String, usize> = BTreeMap::new();
+    let blocks = basic_blocks(&blob);
+    for block in &blocks {
+        for opcode in &block.opcodes {
+            *opcodes.entry(opcode_name(*opcode)).or_default() += 1;
+        }
+    }
+
+    let imports = blob.imports();
+    let inspection = Inspection {
+        program: args.program.display().to_string(),
+        blob_size: raw_blob.len(),
+        is_64_bit: blob.is_64_bit(),
+        code_size: blob.code().len(),
+        ro_data_size: blob.ro_data().len(),
+        ro_data_region_size: blob.ro_data_size(),
+        rw_data_size: blob.rw_data().len(),
+        rw_data_region_size: blob.rw_data_size(),
+        stack_size: blob.stack_size(),
+        exports: blob
+            .exports()
+            .map(|export| Symbol {
+                name: export.symbol().to_string(),
+                offset: export.program_counter().0,
+            })
+            .collect(),
+        imports: (0..imports.len())
+            .map(|nth| match imports.get(nth) {
+                Some(symbol) => symbol.to_string(),
+                None => String::from("<invalid>"),
+            })
+            .collect(),
+        instructions: opcodes.values().sum(),
+        basic_blocks: blocks.len(),
+        opcodes,
+        memory_map: MemoryLayout {
+            page_size: memory_map.page_size(),
+            ro_data_address: memory_map.ro_data_address(),
+            ro_data_size: memory_map.ro_data_size(),
+            rw_data_address: memory_map.rw_data_address(),
+            rw_data_size: memory_map.rw_data_size(),
+            heap_base: memory_map.heap_base(),
+            max_heap_size: memory_map.max_heap_size(),
+            stack_address_low: memory_map.stack_address_low(),
+            stack_address_high: memory_map.stack_address_high(),
+            stack_size: memory_map.stack_size(),
+            aux_data_address: memory_map.aux_data_address(),
+            aux_data_size: memory_map.aux_data_size(),
+        },
+    };
+
+    print_inspection(&inspection);
+
+    if let Some(path) = args.output {
+        let json = serde_json::to_string_pretty(&inspection)
+            .map_err(|error| format!("failed to serialize the report: {error}"))?;
+        std::fs::write(&path, json)
+            .map_err(|error| format!("failed to write {path:?}: {error}"))?;
+    }
+
+    Ok(())
+}
+
+fn print_inspection(inspection: &Inspection) {
+    println!("Program: {}", inspection.program);
+    println!("  Blob size: {} bytes", inspection.blob_size);
+    println!("  64-bit: {}", inspection.is_64_bit);
+    println!("  Code size: {} bytes", inspection.code_size);
+    println!(
+        "  RO data: {} bytes (region: {} bytes)",
+        inspection.ro_data_size, inspection.ro_data_region_size
+    );
+    println!(
+        "  RW data: {} bytes (region: {} bytes)",
+        inspection.rw_data_size, inspection.rw_data_region_size
+    );
+    println!("  Stack size: {} bytes", inspection.stack_size);
+    println!("  Instructions: {}", inspection.instructions);
+    println!("  Basic blocks: {}", inspection.basic_blocks);
+    println!();
+
+    println!("Exports:");
+    for export in &inspection.exports {
+        println!("  {:<40} pc = {}", export.name, export.offset);
+    }
+    println!();
+
+    println!("Imports:");
+    for (nth, import) in inspection.imports.iter().enumerate() {
+        println!("  {:>4}: {}", nth, import);
+    }
+    println!();
+
+    let layout = &inspection.memory_map;
+    let region = |name: &str, address: u32, size: u32| {
+        println!(
+            "  {:<10} 0x{:08x}..0x{:08x} ({} bytes)",
+            name,
+            address,
+            u64::from(address) + u64::from(size),
+            size
+        );
+    };
+
+    println!("Memory map (page size: {}):", layout.page_size);
+    region("RO data", layout.ro_data_address, layout.ro_data_size);
+    region("RW data", layout.rw_data_address, layout.rw_data_size);
+    region("Heap", layout.heap_base, layout.max_heap_size);
+    region("Stack", layout.stack_address_low, layout.stack_size);
+    region("Aux data", layout.aux_data_address, layout.aux_data_size);
+    println!();
+
+    let mut opcodes: Vec<(&String, &usize)> = inspection.opcodes.iter().collect();
+    opcodes.sort_by(|lhs, rhs| rhs.1.cmp(lhs.1).then(lhs.0.cmp(rhs.0)));
+
+    println!("Instruction mix:");
+    for (name, &count) in opcodes {
+        let percent = percent(count as u64, inspection.instructions as u64);
+        println!("  {:<40} {:>10} {:>6.2}%", name, count, percent);
+    }
+}
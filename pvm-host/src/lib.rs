@@ -0,0 +1,85 @@
+//! Runs PolkaVM programs under different gas cost models and measures them.
+//!
+//! The `pvm-host` binary is built on top of this library; other tools can use
+//! [`runner::BenchmarkRunner`] to embed the same measurements.
+
+use polkavm::{Config, GasMeteringKind, ModuleConfig};
+use std::sync::Arc;
+
+pub mod cost_model;
+pub mod environment;
+pub mod host;
+pub mod output;
+pub mod perf;
+pub mod runner;
+pub mod stats;
+
+use crate::runner::ExecutionFailure;
+
+pub use crate::runner::{Benchmark, BenchmarkRunner, Measurement, Outcome, Phases};
+
+#[derive(Debug)]
+pub enum Error {
+    Other(String),
+    Execution(ExecutionFailure),
+}
+
+impl Error {
+    /// The exit code with which the process reports this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(..) => 1,
+            Error::Execution(failure) => failure.exit_code(),
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::Other(error) => fmt.write_str(error),
+            Error::Execution(failure) => failure.fmt(fmt),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(error: String) -> Self {
+        Error::Other(error)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(error: &str) -> Self {
+        Error::Other(error.to_owned())
+    }
+}
+
+impl From<ExecutionFailure> for Error {
+    fn from(failure: ExecutionFailure) -> Self {
+        Error::Execution(failure)
+    }
+}
+
+/// Creates a base configuration which uses the given cost model.
+pub fn config_with_cost_model(cost_model: &Arc<polkavm::CostModel>) -> Result<Config, String> {
+    let mut config =
+        Config::from_env().map_err(|error| format!("invalid configuration: {error}"))?;
+    config.set_allow_experimental(true);
+    config.set_default_cost_model(Some(Arc::clone(cost_model).into()));
+    Ok(config)
+}
+
+/// Creates a module configuration with the given gas metering and room for the input in the aux data.
+pub fn module_config(
+    gas_metering: Option<GasMeteringKind>,
+    data: &[u8],
+) -> Result<ModuleConfig, String> {
+    let aux_data_size = u32::try_from(data.len())
+        .map_err(|_| format!("the input is too big: {} bytes", data.len()))?;
+
+    let mut module_config = ModuleConfig::default();
+    module_config.set_gas_metering(gas_metering);
+    module_config.set_aux_data_size(aux_data_size);
+    Ok(module_config)
+}
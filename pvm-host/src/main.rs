@@ -1,332 +1,594 @@
-use polkavm::{Config, Engine, InterruptKind, Module, ProgramBlob, Reg};
-use std::sync::Arc;
-
-macro_rules! weights_io {
-    (generate_code_impl
-        $output:ident $model:ident
-        $($inst:ident,)+
-    ) => {
-        let CostModel { $($inst,)+ } = $model;
-        $({
-            writeln!(&mut $output, "    {}: {},", stringify!($inst), $inst).unwrap();
-        })+
-    };
+use clap::Parser;
+use polkavm::{BackendKind, Config, GasMeteringKind, ProgramBlob, SandboxKind};
+use std::path::{Path, PathBuf};
 
-    (generate_json_impl
-        $output:ident $model:ident
-        $($inst:ident,)+
-    ) => {
-        let CostModel { $($inst,)+ } = $model;
-        $({
-            writeln!(&mut $output, "    \"{}\": {},", stringify!($inst), $inst).unwrap();
-        })+
-    };
+mod analysis;
+mod calibrate;
+mod compare;
+mod cost_model_tools;
+mod inspect;
+mod profile;
+mod report;
+mod trace;
+
+use pvm_host::cost_model::{load_cost_models, NamedCostModel, EMBEDDED_COST_MODELS};
+use pvm_host::environment::{pin_to_cpu, Environment};
+use pvm_host::output::{self, BenchmarkResult, OutputFormat, ResultWriter};
+use pvm_host::perf::{Counters, PerfCounters, PhaseCounters};
+use pvm_host::runner::InputStrategy;
+use pvm_host::{config_with_cost_model, module_config, Benchmark, BenchmarkRunner, Error, Phases};
+
+#[derive(Parser, Debug)]
+#[clap(version)]
+enum Args {
+    /// Runs a program under one or more gas cost models and measures its execution time.
+    Run(RunArgs),
+
+    /// Runs a program under every available backend and sandbox and compares the results.
+    CompareBackends(compare::CompareBackendsArgs),
+
+    /// Runs a program without gas metering, and with synchronous and asynchronous gas metering,
+    /// and compares the results.
+    CompareGasMetering(compare::CompareGasMeteringArgs),
+
+    /// Runs a program with a fresh instance for every iteration, and with a single instance
+    /// which is reset in between, and compares the cost of instantiating, resetting and executing.
+    CompareInstantiation(compare::CompareInstantiationArgs),
+
+    /// Runs a program with its input passed through the aux data, the heap, read-only memory
+    /// and a host call, and compares how long passing the input and executing took.
+    CompareInputStrategies(compare::CompareInputStrategiesArgs),
+
+    /// Runs a program under the interpreter with step tracing, and prints how many times each
+    /// instruction was executed and how much gas it accounted for under each cost model.
+    Trace(trace::TraceArgs),
+
+    /// Runs a microbenchmark for each family of instructions and fits a new cost model
+    /// to the measured timings.
+    Calibrate(calibrate::CalibrateArgs),
+
+    /// Relates the gas used to the time taken in previously written results, and reports how
+    /// accurately each cost model predicts the runtime.
+    Report(report::ReportArgs),
+
+    /// Converts a cost model into canonical JSON or into Rust source code.
+    ExportCostModel(cost_model_tools::ExportCostModelArgs),
+
+    /// Compares the weights of two cost models.
+    DiffCostModels(cost_model_tools::DiffCostModelsArgs),
+
+    /// Derives a new cost model from an existing one by interpolating, scaling or overriding
+    /// its weights.
+    DeriveCostModel(cost_model_tools::DeriveCostModelArgs),
+
+    /// Calculates the static gas cost of every basic block and export of a program without
+    /// running it.
+    AnalyzeGas(analysis::AnalyzeGasArgs),
+
+    /// Prints the sizes, exports, instruction mix and memory layout of a program.
+    Inspect(inspect::InspectArgs),
+
+    /// Runs a program with step tracing, and writes out which functions the gas and the time
+    /// were spent in as folded stacks which can be turned into a flamegraph.
+    Profile(profile::ProfileArgs),
+}
+
+/// Which program to run, with what input and under which cost models.
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// The name of the export to call.
+    #[clap(short, long, default_value = "run")]
+    export: String,
+
+    #[clap(flatten)]
+    cost_models: CostModelArgs,
+
+    /// The amount of gas the program starts with.
+    #[clap(short, long, default_value_t = 1_000_000_000_000_000)]
+    gas: i64,
+
+    /// The program blob to run.
+    program: PathBuf,
+
+    /// The file whose contents will be passed to the program as its input.
+    input: PathBuf,
+}
+
+/// The gas cost models to run or analyze a program under.
+#[derive(clap::Args, Debug)]
+struct CostModelArgs {
+    /// The cost model to use; either the name of an embedded model (L1-miss, L2-miss, L3-miss),
+    /// a path to a JSON file, or a path to a directory of JSON files. Can be specified multiple times.
+    /// Defaults to all embedded models.
+    #[clap(short, long = "cost-model", value_name = "MODEL")]
+    cost_models: Vec<String>,
+
+    /// Skip the cost models which fail to load instead of aborting.
+    #[clap(long)]
+    skip_invalid_cost_models: bool,
+}
+
+/// How the input is passed to the program.
+#[derive(clap::Args, Debug)]
+struct InputStrategyArgs {
+    /// How to pass the input to the program. With "host-call" the export must read the input
+    /// through the `read_input` host call.
+    #[clap(long, value_enum, default_value_t = InputStrategyArg::Aux)]
+    input_strategy: InputStrategyArg,
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchmarkArgs {
+    #[clap(flatten)]
+    inputs: InputArgs,
+
+    /// The number of timed iterations.
+    #[clap(short, long, default_value_t = 10)]
+    iterations: u32,
+
+    /// The number of untimed iterations to run before the timed ones.
+    #[clap(short, long, default_value_t = 0)]
+    warmup: u32,
+
+    /// Don't reject outliers when calculating the timing statistics.
+    #[clap(long)]
+    keep_outliers: bool,
+}
+
+/// Whether the timed iterations share a single instance.
+#[derive(clap::Args, Debug)]
+struct InstanceArgs {
+    /// Reuse a single instance for every iteration and reset it in between, instead of
+    /// instantiating the module anew each time.
+    #[clap(long)]
+    reuse_instance: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    #[clap(flatten)]
+    benchmark: BenchmarkArgs,
 
-    (generate_json $output:ident $model:ident) => {
-        weights_io! {
-            call generate_json_impl $output $model
+    #[clap(flatten)]
+    strategy: InputStrategyArgs,
+
+    #[clap(flatten)]
+    instance: InstanceArgs,
+
+    #[clap(flatten)]
+    engine: EngineArgs,
+
+    /// The kind of gas metering to use.
+    #[clap(long, value_enum, default_value_t = GasMeteringArg::Sync)]
+    gas_metering: GasMeteringArg,
+
+    /// The name of the benchmark used in the results. Defaults to "<program>/<input>".
+    #[clap(short, long)]
+    name: Option<String>,
+
+    /// Append the results to this file in a machine-readable format.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// The format in which to write the results.
+    #[clap(long, value_enum, default_value_t = OutputFormatArg::Json)]
+    output_format: OutputFormatArg,
+
+    /// Collect the hardware performance counters (cycles, instructions, branch misses, L1d misses
+    /// and LLC misses) around each phase of the timed iterations. Linux only; note that with
+    /// the Linux sandbox the guest program runs in a separate process which isn't counted.
+    #[clap(long)]
+    perf_counters: bool,
+
+    /// Pin the benchmark to this core. The machine and software the benchmark was run with
+    /// are recorded in the results either way.
+    #[clap(long, value_name = "CORE")]
+    pin_cpu: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct EngineArgs {
+    /// The backend to use. Defaults to the one selected through the POLKAVM_BACKEND environment variable.
+    #[clap(long, value_enum)]
+    backend: Option<BackendArg>,
+
+    /// The sandbox to use. Defaults to the one selected through the POLKAVM_SANDBOX environment variable.
+    #[clap(long, value_enum)]
+    sandbox: Option<SandboxArg>,
+}
+
+impl EngineArgs {
+    fn apply(&self, config: &mut Config) {
+        if let Some(backend) = self.backend {
+            config.set_backend(Some(backend.into()));
         }
-    };
+        if let Some(sandbox) = self.sandbox {
+            config.set_sandbox(Some(sandbox.into()));
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum BackendArg {
+    Interpreter,
+    Compiler,
+}
 
-    (generate_code $output:ident $model:ident) => {
-        weights_io! {
-            call generate_code_impl $output $model
+impl From<BackendArg> for BackendKind {
+    fn from(backend: BackendArg) -> Self {
+        match backend {
+            BackendArg::Interpreter => BackendKind::Interpreter,
+            BackendArg::Compiler => BackendKind::Compiler,
         }
-    };
+    }
+}
 
-    (model_from_map_impl $map:ident $model:ident $($inst:ident,)+) => {
-        $(
-            $model.$inst = $map.remove(stringify!($inst)).ok_or_else(|| format!("missing cost for: '{}'", stringify!($inst)))?;
-        )+
-    };
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum SandboxArg {
+    Linux,
+    Generic,
+}
 
-    (model_from_map $map:ident $model:ident) => {
-        weights_io! {
-            call model_from_map_impl $map $model
+impl From<SandboxArg> for SandboxKind {
+    fn from(sandbox: SandboxArg) -> Self {
+        match sandbox {
+            SandboxArg::Linux => SandboxKind::Linux,
+            SandboxArg::Generic => SandboxKind::Generic,
         }
-    };
+    }
+}
 
-    (call
-        $($args:ident)+
-    ) => {
-        weights_io! {
-            $($args)+
-
-            add_32,
-            add_64,
-            add_imm_32,
-            add_imm_64,
-            and,
-            and_imm,
-            and_inverted,
-            branch_eq,
-            branch_eq_imm,
-            branch_greater_or_equal_signed,
-            branch_greater_or_equal_signed_imm,
-            branch_greater_or_equal_unsigned,
-            branch_greater_or_equal_unsigned_imm,
-            branch_greater_signed_imm,
-            branch_greater_unsigned_imm,
-            branch_less_or_equal_signed_imm,
-            branch_less_or_equal_unsigned_imm,
-            branch_less_signed,
-            branch_less_signed_imm,
-            branch_less_unsigned,
-            branch_less_unsigned_imm,
-            branch_not_eq,
-            branch_not_eq_imm,
-            cmov_if_not_zero,
-            cmov_if_not_zero_imm,
-            cmov_if_zero,
-            cmov_if_zero_imm,
-            count_leading_zero_bits_32,
-            count_leading_zero_bits_64,
-            count_set_bits_32,
-            count_set_bits_64,
-            count_trailing_zero_bits_32,
-            count_trailing_zero_bits_64,
-            div_signed_32,
-            div_signed_64,
-            div_unsigned_32,
-            div_unsigned_64,
-            ecalli,
-            fallthrough,
-            invalid,
-            jump,
-            jump_indirect,
-            load_i16,
-            load_i32,
-            load_i8,
-            load_imm,
-            load_imm64,
-            load_imm_and_jump,
-            load_imm_and_jump_indirect,
-            load_indirect_i16,
-            load_indirect_i32,
-            load_indirect_i8,
-            load_indirect_u16,
-            load_indirect_u32,
-            load_indirect_u64,
-            load_indirect_u8,
-            load_u16,
-            load_u32,
-            load_u64,
-            load_u8,
-            maximum,
-            maximum_unsigned,
-            memset,
-            minimum,
-            minimum_unsigned,
-            move_reg,
-            mul_32,
-            mul_64,
-            mul_imm_32,
-            mul_imm_64,
-            mul_upper_signed_signed,
-            mul_upper_signed_unsigned,
-            mul_upper_unsigned_unsigned,
-            negate_and_add_imm_32,
-            negate_and_add_imm_64,
-            or,
-            or_imm,
-            or_inverted,
-            rem_signed_32,
-            rem_signed_64,
-            rem_unsigned_32,
-            rem_unsigned_64,
-            reverse_byte,
-            rotate_left_32,
-            rotate_left_64,
-            rotate_right_32,
-            rotate_right_64,
-            rotate_right_imm_32,
-            rotate_right_imm_64,
-            rotate_right_imm_alt_32,
-            rotate_right_imm_alt_64,
-            sbrk,
-            set_greater_than_signed_imm,
-            set_greater_than_unsigned_imm,
-            set_less_than_signed,
-            set_less_than_signed_imm,
-            set_less_than_unsigned,
-            set_less_than_unsigned_imm,
-            shift_arithmetic_right_32,
-            shift_arithmetic_right_64,
-            shift_arithmetic_right_imm_32,
-            shift_arithmetic_right_imm_64,
-            shift_arithmetic_right_imm_alt_32,
-            shift_arithmetic_right_imm_alt_64,
-            shift_logical_left_32,
-            shift_logical_left_64,
-            shift_logical_left_imm_32,
-            shift_logical_left_imm_64,
-            shift_logical_left_imm_alt_32,
-            shift_logical_left_imm_alt_64,
-            shift_logical_right_32,
-            shift_logical_right_64,
-            shift_logical_right_imm_32,
-            shift_logical_right_imm_64,
-            shift_logical_right_imm_alt_32,
-            shift_logical_right_imm_alt_64,
-            sign_extend_16,
-            sign_extend_8,
-            store_imm_indirect_u16,
-            store_imm_indirect_u32,
-            store_imm_indirect_u64,
-            store_imm_indirect_u8,
-            store_imm_u16,
-            store_imm_u32,
-            store_imm_u64,
-            store_imm_u8,
-            store_indirect_u16,
-            store_indirect_u32,
-            store_indirect_u64,
-            store_indirect_u8,
-            store_u16,
-            store_u32,
-            store_u64,
-            store_u8,
-            sub_32,
-            sub_64,
-            trap,
-            xnor,
-            xor,
-            xor_imm,
-            zero_extend_16,
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum GasMeteringArg {
+    None,
+    Sync,
+    Async,
+}
+
+impl GasMeteringArg {
+    fn to_kind(self) -> Option<GasMeteringKind> {
+        match self {
+            GasMeteringArg::None => None,
+            GasMeteringArg::Sync => Some(GasMeteringKind::Sync),
+            GasMeteringArg::Async => Some(GasMeteringKind::Async),
         }
-    };
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum InputStrategyArg {
+    Aux,
+    Heap,
+    ReadOnly,
+    HostCall,
+}
+
+impl From<InputStrategyArg> for InputStrategy {
+    fn from(strategy: InputStrategyArg) -> Self {
+        match strategy {
+            InputStrategyArg::Aux => InputStrategy::Aux,
+            InputStrategyArg::Heap => InputStrategy::Heap,
+            InputStrategyArg::ReadOnly => InputStrategy::ReadOnly,
+            InputStrategyArg::HostCall => InputStrategy::HostCall,
+        }
+    }
 }
 
-use std::collections::BTreeMap;
+#[derive(Copy, Clone, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum OutputFormatArg {
+    Json,
+    Csv,
+}
 
-fn deserialize_cost_model_from_map(
-    mut map: BTreeMap<String, u32>,
-) -> Result<polkavm::CostModel, String> {
-    let mut cost_model = polkavm::CostModel::naive();
-    weights_io! {
-        model_from_map map cost_model
+impl From<OutputFormatArg> for OutputFormat {
+    fn from(format: OutputFormatArg) -> Self {
+        match format {
+            OutputFormatArg::Json => OutputFormat::Json,
+            OutputFormatArg::Csv => OutputFormat::Csv,
+        }
     }
+}
+
+/// Everything loaded from the disk which is necessary to run a benchmark.
+struct Inputs {
+    raw_blob: Vec<u8>,
+    blob: ProgramBlob,
+    data: Vec<u8>,
+    cost_models: Vec<NamedCostModel>,
+}
+
+impl InputArgs {
+    fn load(&self) -> Result<Inputs, String> {
+        let raw_blob = std::fs::read(&self.program)
+            .map_err(|error| format!("failed to read {:?}: {error}", self.program))?;
+        let data = std::fs::read(&self.input)
+            .map_err(|error| format!("failed to read {:?}: {error}", self.input))?;
+        let blob = ProgramBlob::parse(raw_blob[..].into())
+            .map_err(|error| format!("failed to parse {:?}: {error}", self.program))?;
+
+        let cost_models = self.cost_models.load()?;
 
-    if !map.is_empty() {
-        let extra_keys: Vec<_> = map.into_keys().map(|key| format!("'{}'", key)).collect();
-        let extra_keys = extra_keys.join(", ");
-        return Err(format!(
-            "failed to deserialize cost model: extra keys: {extra_keys}"
-        ));
+        Ok(Inputs {
+            raw_blob,
+            blob,
+            data,
+            cost_models,
+        })
     }
 
-    Ok(cost_model)
+    /// Returns a benchmark which runs the program exactly once.
+    fn single_run<'a>(&'a self, inputs: &'a Inputs) -> Benchmark<'a> {
+        Benchmark {
+            blob: &inputs.blob,
+            export: &self.export,
+            data: &inputs.data,
+            gas: self.gas,
+            warmup: 0,
+            iterations: 0,
+            reject_outliers: false,
+            perf: None,
+            reuse_instance: false,
+            input_strategy: InputStrategy::default(),
+        }
+    }
+
+    /// Returns the default name of the benchmark, in the form of "<program>/<input>".
+    fn default_name(&self) -> String {
+        let stem = |path: &Path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+
+        format!("{}/{}", stem(&self.program), stem(&self.input))
+    }
+}
+
+impl BenchmarkArgs {
+    fn load(&self) -> Result<Inputs, String> {
+        self.inputs.load()
+    }
+
+    fn benchmark<'a>(&'a self, inputs: &'a Inputs) -> Benchmark<'a> {
+        Benchmark {
+            warmup: self.warmup,
+            iterations: self.iterations,
+            reject_outliers: !self.keep_outliers,
+            ..self.inputs.single_run(inputs)
+        }
+    }
+}
+
+impl CostModelArgs {
+    /// Loads the cost models given on the command line, or all of the embedded ones if none were given.
+    fn load(&self) -> Result<Vec<NamedCostModel>, String> {
+        let specs: Vec<&str> = if self.cost_models.is_empty() {
+            EMBEDDED_COST_MODELS.iter().map(|(name, _)| *name).collect()
+        } else {
+            self.cost_models.iter().map(|spec| spec.as_str()).collect()
+        };
+
+        let mut cost_models = Vec::new();
+        let mut error_count = 0;
+        for spec in specs {
+            for result in load_cost_models(spec) {
+                match result {
+                    Ok(cost_model) => cost_models.push(cost_model),
+                    Err(error) => {
+                        eprintln!("ERROR: {error}");
+                        error_count += 1;
+                    }
+                }
+            }
+        }
+
+        if error_count > 0 && !self.skip_invalid_cost_models {
+            return Err(format!("failed to load {error_count} cost model(s)"));
+        }
+
+        if cost_models.is_empty() {
+            return Err("no cost models to run with".into());
+        }
+
+        Ok(cost_models)
+    }
 }
 
 fn main() {
     env_logger::init();
 
-    let mut args = std::env::args().skip(1);
-    let program_path = args.next().expect("missing program path");
-    let data_path = args.next().expect("missing data path");
+    let args = Args::parse();
+    let result = match args {
+        Args::Run(args) => main_run(args),
+        Args::CompareBackends(args) => compare::main_compare_backends(args),
+        Args::CompareGasMetering(args) => compare::main_compare_gas_metering(args),
+        Args::CompareInstantiation(args) => compare::main_compare_instantiation(args),
+        Args::CompareInputStrategies(args) => compare::main_compare_input_strategies(args),
+        Args::Trace(args) => trace::main_trace(args),
+        Args::Calibrate(args) => calibrate::main_calibrate(args),
+        Args::Report(args) => report::main_report(args),
+        Args::ExportCostModel(args) => cost_model_tools::main_export_cost_model(args),
+        Args::DiffCostModels(args) => cost_model_tools::main_diff_cost_models(args),
+        Args::DeriveCostModel(args) => cost_model_tools::main_derive_cost_model(args),
+        Args::AnalyzeGas(args) => analysis::main_analyze_gas(args),
+        Args::Inspect(args) => inspect::main_inspect(args),
+        Args::Profile(args) => profile::main_profile(args),
+    };
 
-    let raw_blob = std::fs::read(&program_path).unwrap();
-    let data = std::fs::read(&data_path).unwrap();
-    let blob = ProgramBlob::parse(raw_blob[..].into()).unwrap();
+    if let Err(error) = result {
+        eprintln!("ERROR: {error}");
+        std::process::exit(error.exit_code());
+    }
+}
 
-    let mut is_first = true;
+fn main_run(args: RunArgs) -> Result<(), Error> {
+    if let Some(cpu) = args.pin_cpu {
+        pin_to_cpu(cpu)?;
+    }
 
-    for (name, cost_blob) in [
-        ("L1-miss", &include_bytes!("../model-l1-miss.json")[..]),
-        ("L2-miss", &include_bytes!("../model-l2-miss.json")[..]),
-        ("L3-miss", &include_bytes!("../model-l3-miss.json")[..]),
-    ] {
-        println!("Using gas cost model: {name}");
+    let environment = Environment::capture(args.pin_cpu);
+    environment.print();
 
-        let cost_model;
-        if let Some(map) = core::str::from_utf8(&cost_blob)
-            .ok()
-            .and_then(|blob| serde_json::from_str(blob).ok())
-        {
-            let map: BTreeMap<String, u32> = map;
-            cost_model = deserialize_cost_model_from_map(map)
-                .map_err(|error| format!("failed to parse the cost model for {name}: {error}"))
-                .unwrap();
-        } else {
-            panic!("failed to parse the cost model for {name}");
-        }
+    let inputs = args.benchmark.load()?;
+    let perf = args.perf_counters.then(PerfCounters::open).transpose()?;
+    let benchmark = Benchmark {
+        perf: perf.as_ref(),
+        reuse_instance: args.instance.reuse_instance,
+        input_strategy: args.strategy.input_strategy.into(),
+        ..args.benchmark.benchmark(&inputs)
+    };
 
-        let mut config = Config::from_env().unwrap();
-        let cost_model = Arc::new(cost_model);
-        config.set_allow_experimental(true);
-        config.set_default_cost_model(Some(cost_model.into()));
+    let benchmark_name = args
+        .name
+        .clone()
+        .unwrap_or_else(|| args.benchmark.inputs.default_name());
+    let blob_hash = output::hash(&inputs.raw_blob);
+    let input_hash = output::hash(&inputs.data);
 
-        let engine = Engine::new(&config).unwrap();
+    let mut writer = args
+        .output
+        .as_deref()
+        .map(|path| ResultWriter::new(path, args.output_format.into()))
+        .transpose()?;
 
-        let mut config = polkavm::ModuleConfig::default();
-        config.set_gas_metering(Some(polkavm::GasMeteringKind::Sync));
-        config.set_aux_data_size(data.len().try_into().unwrap());
-        let module = Module::from_blob(&engine, &config, blob.clone()).unwrap();
-        let aux_address = module.memory_map().aux_data_address();
+    // A failure under one cost model doesn't prevent running under the others.
+    let mut first_failure = None;
+    for NamedCostModel { name, cost_model } in &inputs.cost_models {
+        println!("Using gas cost model: {name}");
+
+        let mut config = config_with_cost_model(cost_model)?;
+        args.engine.apply(&mut config);
 
-        let entry_point = module
-            .exports()
-            .find(|export| export == "run")
-            .unwrap()
-            .program_counter();
+        let module_config = module_config(args.gas_metering.to_kind(), &inputs.data)?;
 
         println!("  Starting...");
-        let instant = std::time::Instant::now();
-        let mut instance = module.instantiate().unwrap();
-        instance.set_next_program_counter(entry_point);
-        instance.set_reg(Reg::RA, polkavm::RETURN_TO_HOST);
-        instance.set_reg(Reg::SP, module.default_sp());
-        instance.write_memory(aux_address, &data).unwrap();
-        instance.set_reg(Reg::A0, u64::from(aux_address));
-        instance.set_reg(Reg::A1, data.len() as u64);
-        let initial_gas = 1000000000000000;
-        instance.set_gas(initial_gas);
-
-        loop {
-            let interrupt_kind = instance.run().unwrap();
-            match interrupt_kind {
-                InterruptKind::Finished => break,
-                _ => panic!("unexpected interruption: {interrupt_kind:?}"),
+        let measurement = match BenchmarkRunner::new(&config, &module_config, &benchmark)
+            .and_then(|mut runner| runner.measure())
+        {
+            Ok(measurement) => measurement,
+            Err(Error::Execution(failure)) => {
+                println!("  FAILED: {failure}");
+                println!();
+                first_failure.get_or_insert(failure);
+                continue;
             }
-        }
+            Err(error) => return Err(error),
+        };
 
-        let a0 = instance.reg(Reg::A0);
+        let a0 = measurement.result;
         println!("  Result: 0x{:x}", a0);
 
-        let gas_used = initial_gas - instance.gas();
+        let gas_used = measurement.gas_used;
         println!("  Gas used: {}", gas_used);
 
-        let elapsed = instant.elapsed().as_secs_f64();
-        println!("  Initial run elapsed: {elapsed}s");
-
-        core::mem::drop(instance);
-        if is_first {
-            let instant = std::time::Instant::now();
-            is_first = false;
-            for _ in 0..10 {
-                let mut instance = module.instantiate().unwrap();
-                instance.set_next_program_counter(entry_point);
-                instance.set_reg(Reg::RA, polkavm::RETURN_TO_HOST);
-                instance.set_reg(Reg::SP, module.default_sp());
-                instance.write_memory(aux_address, &data).unwrap();
-                instance.set_reg(Reg::A0, u64::from(aux_address));
-                instance.set_reg(Reg::A1, data.len() as u64);
-                let initial_gas = 1000000000000000;
-                instance.set_gas(initial_gas);
-
-                loop {
-                    let interrupt_kind = instance.run().unwrap();
-                    match interrupt_kind {
-                        InterruptKind::Finished => break,
-                        _ => panic!("unexpected interruption: {interrupt_kind:?}"),
-                    }
-                }
+        if !measurement.output.is_empty() {
+            println!("  Output: {} bytes", measurement.output.len());
+        }
+
+        for (name, stats) in &measurement.host_calls {
+            println!(
+                "  Host calls to '{name}': {} ({}s total)",
+                stats.count, stats.elapsed
+            );
+        }
+
+        let initial_run = measurement.initial_phases.execution();
+        println!("  Initial run elapsed: {initial_run}s");
+        print_phases("Initial run phases", &measurement.initial_phases);
+
+        if let Some(ref summary) = measurement.summary {
+            println!("  Elapsed on average: {}s", summary.mean);
+            println!(
+                "  Samples: {} ({} outliers rejected)",
+                summary.samples,
+                summary.outliers.len()
+            );
+            println!(
+                "  Min: {}s, median: {}s, mean: {}s, stddev: {}s",
+                summary.min, summary.median, summary.mean, summary.stddev
+            );
+            println!("  95% CI: [{}s, {}s]", summary.ci_low, summary.ci_high);
+        }
+
+        if !measurement.phases.is_empty() {
+            let median_phases = Phases::median(&measurement.phases);
+            print_phases("Median phases", &median_phases);
+
+            if benchmark.reuse_instance {
+                // The initial run is the only one which instantiates the module.
+                println!(
+                    "  Fresh instantiation: {}s, median reset: {}s, median run: {}s",
+                    measurement.initial_phases.instantiate, median_phases.reset, median_phases.run
+                );
             }
+        }
 
-            let elapsed = instant.elapsed().as_secs_f64() / 10.0;
-            println!("  Elapsed on average: {elapsed}s");
+        if !measurement.counters.is_empty() {
+            print_counters(&PhaseCounters::median(&measurement.counters), gas_used);
         }
 
         println!();
+
+        if let Some(ref mut writer) = writer {
+            writer.write(&BenchmarkResult {
+                benchmark: benchmark_name.clone(),
+                blob_hash: blob_hash.clone(),
+                input_hash: input_hash.clone(),
+                cost_model: name.clone(),
+                gas_used,
+                result: format!("0x{:x}", a0),
+                initial_run,
+                reuse_instance: benchmark.reuse_instance,
+                input_strategy: benchmark.input_strategy,
+                initial_phases: measurement.initial_phases,
+                timings: measurement.timings,
+                phases: measurement.phases,
+                summary: measurement.summary,
+                host_calls: measurement.host_calls,
+                counters: measurement.counters,
+                environment: environment.clone(),
+            })?;
+        }
+    }
+
+    match first_failure {
+        Some(failure) => Err(failure.into()),
+        None => Ok(()),
+    }
+}
+
+fn print_phases(title: &str, phases: &Phases) {
+    println!(
+        "  {title}: engine: {}s, compile: {}s, instantiate: {}s, reset: {}s, write input: {}s, run: {}s",
+        phases.engine,
+        phases.compile,
+        phases.instantiate,
+        phases.reset,
+        phases.write_input,
+        phases.run
+    );
+}
+
+fn print_counters(counters: &PhaseCounters, gas_used: i64) {
+    println!("  Median performance counters:");
+    print!("    {:<12}", "Phase");
+    for name in Counters::NAMES {
+        print!(" {:>16}", name);
+    }
+    println!();
+
+    for (phase, counters) in counters.as_array() {
+        print!("    {:<12}", phase);
+        for value in counters.as_array() {
+            match value {
+                Some(value) => print!(" {:>16}", value),
+                None => print!(" {:>16}", "-"),
+            }
+        }
+        println!();
+    }
+
+    if let (Some(cycles), true) = (counters.run.cycles, gas_used > 0) {
+        println!("    Cycles per gas: {:.3}", cycles as f64 / gas_used as f64);
     }
 }
@@ -0,0 +1,238 @@
+use crate::environment::Environment;
+use crate::host::HostCallStats;
+use crate::perf::PhaseCounters;
+use crate::runner::{InputStrategy, Phases};
+use crate::stats::Summary;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+    /// One JSON object per benchmark run, one per line.
+    Json,
+    /// One row per iteration, with a header.
+    Csv,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkResult {
+    pub benchmark: String,
+    pub blob_hash: String,
+    pub input_hash: String,
+    pub cost_model: String,
+    pub gas_used: i64,
+    pub result: String,
+    /// The time it took to execute the initial (cold) run, in seconds.
+    pub initial_run: f64,
+    /// Whether the timed iterations reused a single instance instead of instantiating the module.
+    pub reuse_instance: bool,
+    /// How the input was passed to the program.
+    pub input_strategy: InputStrategy,
+    pub initial_phases: Phases,
+    /// The time each timed iteration took to execute, in seconds.
+    pub timings: Vec<f64>,
+    /// The per-phase breakdown of each timed iteration.
+    pub phases: Vec<Phases>,
+    pub summary: Option<Summary>,
+    /// The host calls made during the initial run.
+    pub host_calls: BTreeMap<&'static str, HostCallStats>,
+    /// The hardware performance counters of each timed iteration; only written as JSON.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub counters: Vec<PhaseCounters>,
+    /// The machine and software the benchmark was run with.
+    pub environment: Environment,
+}
+
+const CSV_HEADER: &str = "benchmark,blob_hash,input_hash,cost_model,gas_used,result,iteration,elapsed,outlier,engine,compile,instantiate,reset,write_input,run,hostname,cpu_model,pinned_cpu,governor,turbo,kernel,polkavm_version,rustc_version,git_commit,git_dirty";
+
+/// Appends benchmark results to a file in a machine-readable format.
+pub struct ResultWriter {
+    file: File,
+    format: OutputFormat,
+}
+
+impl ResultWriter {
+    pub fn new(path: &Path, format: OutputFormat) -> Result<Self, String> {
+        let mut file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|error| format!("failed to open {path:?}: {error}"))?;
+
+        let is_empty = file
+            .metadata()
+            .map_err(|error| format!("failed to query {path:?}: {error}"))?
+            .len()
+            == 0;
+
+        if format == OutputFormat::Csv && is_empty {
+            writeln!(file, "{CSV_HEADER}")
+                .map_err(|error| format!("failed to write to {path:?}: {error}"))?;
+        }
+
+        Ok(ResultWriter { file, format })
+    }
+
+    pub fn write(&mut self, result: &BenchmarkResult) -> Result<(), String> {
+        let mut output = String::new();
+        match self.format {
+            OutputFormat::Json => {
+                output = serde_json::to_string(result).unwrap();
+                output.push('\n');
+            }
+            OutputFormat::Csv => {
+                let prefix = [
+                    csv_escape(&result.benchmark),
+                    csv_escape(&result.blob_hash),
+                    csv_escape(&result.input_hash),
+                    csv_escape(&result.cost_model),
+                    result.gas_used.to_string(),
+                    csv_escape(&result.result),
+                ]
+                .join(",");
+
+                let outliers = result
+                    .summary
+                    .as_ref()
+                    .map(|summary| &summary.outliers[..])
+                    .unwrap_or_default();
+
+                let phases_to_csv = |phases: &Phases| {
+                    let phases: Vec<String> = phases
+                        .as_array()
+                        .iter()
+                        .map(|elapsed| elapsed.to_string())
+                        .collect();
+                    phases.join(",")
+                };
+
+                let environment = environment_to_csv(&result.environment);
+                output.push_str(&format!(
+                    "{prefix},initial,{},,{},{environment}\n",
+                    result.initial_run,
+                    phases_to_csv(&result.initial_phases)
+                ));
+
+                for (nth, (elapsed, phases)) in
+                    result.timings.iter().zip(&result.phases).enumerate()
+                {
+                    let is_outlier = outliers.contains(&nth);
+                    output.push_str(&format!(
+                        "{prefix},{nth},{elapsed},{is_outlier},{},{environment}\n",
+                        phases_to_csv(phases)
+                    ));
+                }
+            }
+        }
+
+        self.file
+            .write_all(output.as_bytes())
+            .map_err(|error| format!("failed to write the results: {error}"))
+    }
+}
+
+/// Returns the environment as CSV columns, leaving the unknown values empty.
+fn environment_to_csv(environment: &Environment) -> String {
+    let optional = |value: Option<String>| value.as_deref().map(csv_escape).unwrap_or_default();
+    [
+        optional(environment.hostname.clone()),
+        optional(environment.cpu_model.clone()),
+        optional(environment.pinned_cpu.map(|cpu| cpu.to_string())),
+        optional(environment.governor.clone()),
+        optional(environment.turbo.map(|turbo| turbo.to_string())),
+        optional(environment.kernel.clone()),
+        csv_escape(&environment.polkavm_version),
+        csv_escape(&environment.rustc_version),
+        optional(environment.git_commit.clone()),
+        optional(environment.git_dirty.map(|dirty| dirty.to_string())),
+    ]
+    .join(",")
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Returns a hex-encoded SHA-256 hash of the given data.
+pub fn hash(data: &[u8]) -> String {
+    use sha2::Digest;
+
+    let hash = sha2::Sha256::digest(data);
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The subset of a [`BenchmarkResult`] needed to analyze results written in the past.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RecordedResult {
+    pub benchmark: String,
+    pub cost_model: String,
+    pub gas_used: i64,
+    pub initial_run: f64,
+    pub timings: Vec<f64>,
+}
+
+impl RecordedResult {
+    /// The typical execution time of the benchmark, in seconds.
+    pub fn elapsed(&self) -> f64 {
+        if self.timings.is_empty() {
+            self.initial_run
+        } else {
+            crate::stats::median(&self.timings)
+        }
+    }
+}
+
+/// Reads the results from a file written in the JSON format.
+pub fn read_results(path: &Path) -> Result<Vec<RecordedResult>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| format!("failed to read {path:?}: {error}"))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(nth, line)| {
+            serde_json::from_str(line).map_err(|error| {
+                format!(
+                    "failed to parse the result on line {} of {path:?}: {error}",
+                    nth + 1
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escape_leaves_plain_values_alone() {
+        assert_eq!(csv_escape(""), "");
+        assert_eq!(csv_escape("sha1/1m"), "sha1/1m");
+    }
+
+    #[test]
+    fn csv_escape_quotes_commas() {
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+    }
+
+    #[test]
+    fn csv_escape_doubles_quotes() {
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("\""), "\"\"\"\"");
+    }
+
+    #[test]
+    fn csv_escape_quotes_newlines() {
+        assert_eq!(csv_escape("a\nb"), "\"a\nb\"");
+        assert_eq!(csv_escape("a\r\nb"), "\"a\r\nb\"");
+    }
+}
@@ -0,0 +1,339 @@
+use serde::Serialize;
+
+/// The values of the hardware performance counters over a single phase of a run.
+///
+/// A counter is `None` if it isn't supported on this machine.
+#[derive(Copy, Clone, Default, Debug, Serialize)]
+pub struct Counters {
+    pub cycles: Option<u64>,
+    pub instructions: Option<u64>,
+    pub branch_misses: Option<u64>,
+    pub l1d_misses: Option<u64>,
+    pub llc_misses: Option<u64>,
+}
+
+impl Counters {
+    pub const NAMES: [&'static str; 5] = [
+        "cycles",
+        "instructions",
+        "branch_misses",
+        "l1d_misses",
+        "llc_misses",
+    ];
+
+    pub fn as_array(&self) -> [Option<u64>; 5] {
+        [
+            self.cycles,
+            self.instructions,
+            self.branch_misses,
+            self.l1d_misses,
+            self.llc_misses,
+        ]
+    }
+
+    fn from_array(values: [Option<u64>; 5]) -> Self {
+        let [cycles, instructions, branch_misses, l1d_misses, llc_misses] = values;
+        Counters {
+            cycles,
+            instructions,
+            branch_misses,
+            l1d_misses,
+            llc_misses,
+        }
+    }
+
+    /// Returns the per-counter median of the given runs.
+    pub fn median(runs: &[Counters]) -> Counters {
+        let mut values = [None; 5];
+        for (nth, value) in values.iter_mut().enumerate() {
+            let samples: Vec<f64> = runs
+                .iter()
+                .filter_map(|counters| counters.as_array()[nth])
+                .map(|value| value as f64)
+                .collect();
+            if !samples.is_empty() {
+                *value = Some(crate::stats::median(&samples).round() as u64);
+            }
+        }
+
+        Counters::from_array(values)
+    }
+}
+
+/// The counters of each phase of a single benchmark run.
+#[derive(Copy, Clone, Default, Debug, Serialize)]
+pub struct PhaseCounters {
+    pub engine: Counters,
+    pub compile: Counters,
+    pub instantiate: Counters,
+    pub reset: Counters,
+    pub write_input: Counters,
+    pub run: Counters,
+}
+
+impl PhaseCounters {
+    pub fn as_array(&self) -> [(&'static str, Counters); 6] {
+        [
+            ("engine", self.engine),
+            ("compile", self.compile),
+            ("instantiate", self.instantiate),
+            ("reset", self.reset),
+            ("write_input", self.write_input),
+            ("run", self.run),
+        ]
+    }
+
+    /// Returns the per-phase median of the given runs.
+    pub fn median(runs: &[PhaseCounters]) -> PhaseCounters {
+        let median = |get: fn(&PhaseCounters) -> Counters| {
+            let samples: Vec<Counters> = runs.iter().map(get).collect();
+            Counters::median(&samples)
+        };
+
+        PhaseCounters {
+            engine: median(|counters| counters.engine),
+            compile: median(|counters| counters.compile),
+            instantiate: median(|counters| counters.instantiate),
+            reset: median(|counters| counters.reset),
+            write_input: median(|counters| counters.write_input),
+            run: median(|counters| counters.run),
+        }
+    }
+}
+
+/// A set of hardware performance counters measuring the current process.
+///
+/// Note that with the compiler backend and the Linux sandbox the program runs in a separate
+/// process which isn't measured; use the generic sandbox or the interpreter instead.
+pub struct PerfCounters {
+    counters: [Option<sys::Counter>; 5],
+}
+
+impl PerfCounters {
+    /// Opens every supported counter; fails if none of them are.
+    pub fn open() -> Result<Self, String> {
+        let mut errors = Vec::new();
+        let counters =
+            sys::EVENTS.map(
+                |(name, kind, config)| match sys::Counter::open(kind, config) {
+                    Ok(counter) => Some(counter),
+                    Err(error) => {
+                        errors.push(format!("{name}: {error}"));
+                        None
+                    }
+                },
+            );
+
+        if counters.iter().all(Option::is_none) {
+            return Err(format!(
+                "failed to open any performance counters: {}",
+                errors.join(", ")
+            ));
+        }
+
+        for error in errors {
+            eprintln!("WARNING: performance counter unavailable: {error}");
+        }
+
+        Ok(PerfCounters { counters })
+    }
+
+    /// Resets and starts all of the counters.
+    pub fn start(&self) {
+        for counter in self.counters.iter().flatten() {
+            counter.start();
+        }
+    }
+
+    /// Stops all of the counters and returns their values.
+    pub fn stop(&self) -> Counters {
+        for counter in self.counters.iter().flatten() {
+            counter.stop();
+        }
+
+        Counters::from_array(
+            self.counters
+                .each_ref()
+                .map(|counter| counter.as_ref().and_then(sys::Counter::read)),
+        )
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_TYPE_HW_CACHE: u32 = 3;
+
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+    const PERF_COUNT_HW_CACHE_L1D: u64 = 0;
+    const PERF_COUNT_HW_CACHE_LL: u64 = 2;
+    const PERF_COUNT_HW_CACHE_OP_READ: u64 = 0;
+    const PERF_COUNT_HW_CACHE_RESULT_MISS: u64 = 1;
+
+    const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+    const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+    const PERF_EVENT_IOC_ENABLE: u64 = 0x2400;
+    const PERF_EVENT_IOC_DISABLE: u64 = 0x2401;
+    const PERF_EVENT_IOC_RESET: u64 = 0x2403;
+
+    const fn cache_miss(cache: u64) -> u64 {
+        cache | (PERF_COUNT_HW_CACHE_OP_READ << 8) | (PERF_COUNT_HW_CACHE_RESULT_MISS << 16)
+    }
+
+    /// The events backing each of the fields of `Counters`, in order.
+    pub const EVENTS: [(&str, u32, u64); 5] = [
+        ("cycles", PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES),
+        (
+            "instructions",
+            PERF_TYPE_HARDWARE,
+            PERF_COUNT_HW_INSTRUCTIONS,
+        ),
+        (
+            "branch_misses",
+            PERF_TYPE_HARDWARE,
+            PERF_COUNT_HW_BRANCH_MISSES,
+        ),
+        (
+            "l1d_misses",
+            PERF_TYPE_HW_CACHE,
+            cache_miss(PERF_COUNT_HW_CACHE_L1D),
+        ),
+        (
+            "llc_misses",
+            PERF_TYPE_HW_CACHE,
+            cache_miss(PERF_COUNT_HW_CACHE_LL),
+        ),
+    ];
+
+    /// The leading part of the kernel's `struct perf_event_attr`, padded to its full size.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        kind: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        rest: [u64; 10],
+    }
+
+    pub struct Counter {
+        fd: OwnedFd,
+    }
+
+    impl Counter {
+        pub fn open(kind: u32, config: u64) -> Result<Self, String> {
+            let attr = PerfEventAttr {
+                kind,
+                size: core::mem::size_of::<PerfEventAttr>() as u32,
+                config,
+                read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+                flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                ..PerfEventAttr::default()
+            };
+
+            // SAFETY: The attribute is valid for the duration of the call.
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_perf_event_open,
+                    &attr as *const PerfEventAttr,
+                    0 as libc::pid_t,
+                    -1 as libc::c_int,
+                    -1 as libc::c_int,
+                    PERF_FLAG_FD_CLOEXEC,
+                )
+            };
+
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+
+            // SAFETY: The descriptor was just opened and isn't owned by anything else.
+            Ok(Counter {
+                fd: unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) },
+            })
+        }
+
+        fn ioctl(&self, request: u64) {
+            // SAFETY: These requests don't take a pointer argument.
+            unsafe {
+                libc::ioctl(self.fd.as_raw_fd(), request as _, 0);
+            }
+        }
+
+        pub fn start(&self) {
+            self.ioctl(PERF_EVENT_IOC_RESET);
+            self.ioctl(PERF_EVENT_IOC_ENABLE);
+        }
+
+        pub fn stop(&self) {
+            self.ioctl(PERF_EVENT_IOC_DISABLE);
+        }
+
+        /// Reads the value of the counter, scaled up if the kernel had to multiplex it.
+        pub fn read(&self) -> Option<u64> {
+            let mut buffer = [0_u64; 3];
+            // SAFETY: The buffer is valid for writes of its whole size.
+            let length = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    core::mem::size_of_val(&buffer),
+                )
+            };
+
+            if length != core::mem::size_of_val(&buffer) as isize {
+                return None;
+            }
+
+            let [value, enabled, running] = buffer;
+            if running == 0 {
+                return None;
+            }
+
+            Some((u128::from(value) * u128::from(enabled) / u128::from(running)) as u64)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub const EVENTS: [(&str, u32, u64); 5] = [
+        ("cycles", 0, 0),
+        ("instructions", 0, 0),
+        ("branch_misses", 0, 0),
+        ("l1d_misses", 0, 0),
+        ("llc_misses", 0, 0),
+    ];
+
+    pub struct Counter;
+
+    impl Counter {
+        pub fn open(_kind: u32, _config: u64) -> Result<Self, String> {
+            Err("performance counters are only supported on Linux".into())
+        }
+
+        pub fn start(&self) {}
+
+        pub fn stop(&self) {}
+
+        pub fn read(&self) -> Option<u64> {
+            None
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::trace::percent;
+use crate::{InputArgs, InputStrategyArgs};
+use polkavm::program::{Opcode, ISA64_V1};
+use polkavm::{BackendKind, GasMeteringKind, ProgramBlob, ProgramCounter};
+use pvm_host::runner::{Benchmark, BenchmarkRunner};
+use pvm_host::{config_with_cost_model, module_config, Error};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(clap::Args, Debug)]
+pub struct ProfileArgs {
+    #[clap(flatten)]
+    inputs: InputArgs,
+
+    #[clap(flatten)]
+    strategy: InputStrategyArgs,
+
+    /// The prefix of the output files; the profiles are written to "<PREFIX>.gas.folded"
+    /// and "<PREFIX>.time.folded".
+    #[clap(short, long, default_value = "profile")]
+    output_prefix: PathBuf,
+
+    /// The number of the most expensive functions to list.
+    #[clap(long, default_value_t = 20)]
+    top: usize,
+}
+
+/// What an instruction of the program does, as far as the profiler is concerned.
+struct InstructionInfo {
+    /// The gas charged for this instruction.
+    gas: u64,
+    /// If this instruction is a call, the address the callee will return to.
+    return_address: Option<u32>,
+}
+
+#[derive(Copy, Clone, Default)]
+struct Sample {
+    gas: u64,
+    /// The time spent executing, in nanoseconds.
+    time: u64,
+}
+
+/// Tracks the call stack of the guest by watching for calls, and for when execution reaches
+/// the return address of the innermost call.
+struct CallStack {
+    /// The return address and the offset of the call instruction of every active call.
+    frames: Vec<(u32, u32)>,
+    /// Every distinct stack of call instructions seen so far, interned to keep the samples cheap.
+    ids: HashMap<Vec<u32>, usize>,
+    stacks: Vec<Vec<u32>>,
+    current: usize,
+}
+
+impl CallStack {
+    fn new() -> Self {
+        CallStack {
+            frames: Vec::new(),
+            ids: HashMap::from([(Vec::new(), 0)]),
+            stacks: vec![Vec::new()],
+            current: 0,
+        }
+    }
+
+    fn update_id(&mut self) {
+        let call_sites: Vec<u32> = self
+            .frames
+            .iter()
+            .map(|(_, call_site)| *call_site)
+            .collect();
+        let next_id = self.stacks.len();
+        self.current = *self.ids.entry(call_sites.clone()).or_insert(next_id);
+        if self.current == next_id {
+            self.stacks.push(call_sites);
+        }
+    }
+
+    fn push(&mut self, return_address: u32, call_site: u32) {
+        self.frames.push((return_address, call_site));
+        self.update_id();
+    }
+
+    fn pop_if_returned_to(&mut self, program_counter: u32) {
+        if self
+            .frames
+            .last()
+            .map(|(return_address, _)| *return_address)
+            == Some(program_counter)
+        {
+            self.frames.pop();
+            self.update_id();
+        }
+    }
+}
+
+/// Returns the names of the functions the instruction at the given offset belongs to,
+/// outermost first, or `None` if the program has no debug info for it.
+fn symbolize(
+    blob: &ProgramBlob,
+    program_counter: ProgramCounter,
+) -> Result<Option<Vec<String>>, String> {
+    let error = |error| format!("failed to read the debug info at {program_counter}: {error}");
+    let Some(mut line_program) = blob
+        .get_debug_line_program_at(program_counter)
+        .map_err(error)?
+    else {
+        return Ok(None);
+    };
+
+    while let Some(region) = line_program.run().map_err(error)? {
+        if !region.instruction_range().contains(&program_counter) {
+            continue;
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for frame in region.frames() {
+            let name = frame.full_name().map_err(error)?.to_string();
+            // Each line within an inlined function gets its own frame.
+            if names.last() != Some(&name) {
+                names.push(name);
+            }
+        }
+
+        return Ok(Some(names));
+    }
+
+    Ok(None)
+}
+
+/// Turns program counters into function names, caching the results.
+struct Symbolizer<'a> {
+    blob: &'a ProgramBlob,
+    cache: HashMap<u32, Vec<String>>,
+    missing_debug_info: bool,
+}
+
+impl<'a> Symbolizer<'a> {
+    fn frames(&mut self, program_counter: u32) -> Result<&[String], String> {
+        if !self.cache.contains_key(&program_counter) {
+            let frames = match symbolize(self.blob, ProgramCounter(program_counter))? {
+                Some(frames) if !frames.is_empty() => frames,
+                _ => {
+                    self.missing_debug_info = true;
+                    vec![format!("0x{program_counter:x}")]
+                }
+            };
+
+            self.cache.insert(program_counter, frames);
+        }
+
+        Ok(&self.cache[&program_counter])
+    }
+}
+
+pub fn main_profile(args: ProfileArgs) -> Result<(), Error> {
+    let inputs = args.inputs.load()?;
+    let benchmark = Benchmark {
+        input_strategy: args.strategy.input_strategy.into(),
+        ..args.inputs.single_run(&inputs)
+    };
+    let cost_model = &inputs.cost_models[0];
+    if inputs.cost_models.len() > 1 {
+        println!(
+            "WARNING: only the first cost model ({}) is used for profiling",
+            cost_model.name
+        );
+    }
+
+    let mut config = config_with_cost_model(&cost_model.cost_model)?;
+    config.set_backend(Some(BackendKind::Interpreter));
+
+    let mut module_config = module_config(Some(GasMeteringKind::Sync), &inputs.data)?;
+    module_config.set_step_tracing(true);
+
+    let instructions: HashMap<ProgramCounter, InstructionInfo> = inputs
+        .blob
+        .instructions(ISA64_V1)
+        .map(|instruction| {
+            let opcode = instruction.opcode();
+            let is_call = matches!(
+                opcode,
+                Opcode::load_imm_and_jump | Opcode::load_imm_and_jump_indirect
+            );
+            let info = InstructionInfo {
+                gas: u64::from(cost_model.cost_model.cost_for_opcode(opcode)),
+                return_address: is_call.then_some(instruction.next_offset.0),
+            };
+
+            (instruction.offset, info)
+        })
+        .collect();
+
+    // The time between two steps is attributed to the earlier one. Note that this includes
+    // the overhead of the tracing itself, so the times are only meaningful relative to each other.
+    let mut call_stack = CallStack::new();
+    let mut samples: HashMap<(usize, u32), Sample> = HashMap::new();
+    let mut previous: Option<((usize, u32), Instant)> = None;
+
+    println!("Profiling under {}...", cost_model.name);
+    let mut runner = BenchmarkRunner::new(&config, &module_config, &benchmark)?;
+    let outcome = runner.run_traced(&mut |instance| {
+        let now = Instant::now();
+        if let Some((key, instant)) = previous.take() {
+            let sample = samples.entry(key).or_default();
+            sample.time += (now - instant).as_nanos() as u64;
+        }
+
+        let Some(program_counter) = instance.program_counter() else {
+            return;
+        };
+
+        call_stack.pop_if_returned_to(program_counter.0);
+        let key = (call_stack.current, program_counter.0);
+        if let Some(info) = instructions.get(&program_counter) {
+            samples.entry(key).or_default().gas += info.gas;
+            if let Some(return_address) = info.return_address {
+                call_stack.push(return_address, program_counter.0);
+            }
+        }
+
+        previous = Some((key, now));
+    })?;
+
+    println!("Result: 0x{:x}", outcome.result);
+    println!("Gas used: {}", outcome.gas_used);
+
+    let mut symbolizer = Symbolizer {
+        blob: &inputs.blob,
+        cache: HashMap::new(),
+        missing_debug_info: false,
+    };
+
+    let mut folded: BTreeMap<String, Sample> = BTreeMap::new();
+    let mut by_function: BTreeMap<String, Sample> = BTreeMap::new();
+    for ((stack_id, program_counter), sample) in samples {
+        let mut frames: Vec<String> = Vec::new();
+        for &call_site in &call_stack.stacks[stack_id] {
+            frames.extend_from_slice(symbolizer.frames(call_site)?);
+        }
+        frames.extend_from_slice(symbolizer.frames(program_counter)?);
+
+        let function = by_function
+            .entry(frames.last().unwrap().clone())
+            .or_default();
+        function.gas += sample.gas;
+        function.time += sample.time;
+
+        let total = folded.entry(frames.join(";")).or_default();
+        total.gas += sample.gas;
+        total.time += sample.time;
+    }
+
+    if symbolizer.missing_debug_info {
+        println!("WARNING: some of the code has no debug info; link the program without '--strip' to get function names");
+    }
+
+    let write_folded = |extension: &str, value: fn(&Sample) -> u64| -> Result<(), String> {
+        let mut path = args.output_prefix.clone().into_os_string();
+        path.push(extension);
+        let path = PathBuf::from(path);
+
+        let mut output = String::new();
+        for (stack, sample) in &folded {
+            writeln!(&mut output, "{stack} {}", value(sample)).unwrap();
+        }
+
+        std::fs::write(&path, output)
+            .map_err(|error| format!("failed to write {path:?}: {error}"))?;
+        println!("Profile written to {path:?}");
+        Ok(())
+    };
+
+    write_folded(".gas.folded", |sample| sample.gas)?;
+    write_folded(".time.folded", |sample| sample.time)?;
+
+    let total_gas: u64 = by_function.values().map(|sample| sample.gas).sum();
+    let total_time: u64 = by_function.values().map(|sample| sample.time).sum();
+    let mut functions: Vec<(String, Sample)> = by_function.into_iter().collect();
+    functions.sort_by(|lhs, rhs| rhs.1.gas.cmp(&lhs.1.gas).then(lhs.0.cmp(&rhs.0)));
+
+    println!();
+    println!(
+        "  {:>16} {:>7} {:>16} {:>7}  Function",
+        "Self gas", "%", "Self time (ns)", "%"
+    );
+    for (name, sample) in functions.into_iter().take(args.top) {
+        println!(
+            "  {:>16} {:>6.2}% {:>16} {:>6.2}%  {}",
+            sample.gas,
+            percent(sample.gas, total_gas),
+            sample.time,
+            percent(sample.time, total_time),
+            name
+        );
+    }
+
+    Ok(())
+}
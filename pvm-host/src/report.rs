@@ -0,0 +1,199 @@
+use pvm_host::output::read_results;
+use pvm_host::stats::{median, summarize};
+use pvm_host::Error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug)]
+pub struct ReportArgs {
+    /// How far, in percent, a model may be off relative to the other models on a benchmark
+    /// before it's flagged as over- or under-charging.
+    #[clap(long, default_value_t = 10.0)]
+    threshold: f64,
+
+    /// The result files written by `run --output` in the JSON format.
+    #[clap(required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// How well the gas charged by a single cost model tracks the runtime across the suite.
+struct ModelAccuracy<'a> {
+    name: &'a str,
+    benchmarks: usize,
+    /// The typical nanoseconds per gas of this model across all benchmarks.
+    ns_per_gas: f64,
+    /// The coefficient of variation of the nanoseconds per gas across all benchmarks; the lower,
+    /// the better the gas predicts the runtime.
+    variation: f64,
+}
+
+pub fn main_report(args: ReportArgs) -> Result<(), Error> {
+    // Benchmark -> cost model -> nanoseconds per gas of every recorded run.
+    let mut samples: BTreeMap<String, BTreeMap<String, Vec<f64>>> = BTreeMap::new();
+    for path in &args.files {
+        for result in read_results(path)? {
+            if result.gas_used <= 0 {
+                println!(
+                    "WARNING: ignoring '{}' under {} since it didn't use any gas",
+                    result.benchmark, result.cost_model
+                );
+                continue;
+            }
+
+            let ns_per_gas = result.elapsed() * 1_000_000_000.0 / result.gas_used as f64;
+            samples
+                .entry(result.benchmark)
+                .or_default()
+                .entry(result.cost_model)
+                .or_default()
+                .push(ns_per_gas);
+        }
+    }
+
+    if samples.is_empty() {
+        return Err("no results to report on".into());
+    }
+
+    let ns_per_gas: BTreeMap<&str, BTreeMap<&str, f64>> = samples
+        .iter()
+        .map(|(benchmark, models)| {
+            let models = models
+                .iter()
+                .map(|(model, samples)| (model.as_str(), median(samples)))
+                .collect();
+            (benchmark.as_str(), models)
+        })
+        .collect();
+
+    let models: BTreeSet<&str> = ns_per_gas
+        .values()
+        .flat_map(|models| models.keys().copied())
+        .collect();
+
+    let accuracy: Vec<ModelAccuracy> = models
+        .iter()
+        .map(|&name| {
+            let values: Vec<f64> = ns_per_gas
+                .values()
+                .filter_map(|models| models.get(name).copied())
+                .collect();
+            let summary = summarize(&values, false).unwrap();
+            ModelAccuracy {
+                name,
+                benchmarks: values.len(),
+                ns_per_gas: summary.median,
+                variation: summary.stddev / summary.mean,
+            }
+        })
+        .collect();
+
+    let typical: BTreeMap<&str, f64> = accuracy
+        .iter()
+        .map(|model| (model.name, model.ns_per_gas))
+        .collect();
+
+    println!("Nanoseconds per gas, and the deviation from each model's typical value:");
+    println!();
+    print!("  {:<32}", "Benchmark");
+    for model in &models {
+        print!(" {:>26}", model);
+    }
+    println!();
+
+    let threshold = args.threshold / 100.0;
+    let mut flagged = Vec::new();
+    for (benchmark, values) in &ns_per_gas {
+        // If a benchmark is equally off under every model then that's a property of the benchmark
+        // and not of the models, so a model is only flagged if it's off both from its own typical
+        // value and relative to the others.
+        let deviations: BTreeMap<&str, f64> = values
+            .iter()
+            .map(|(&model, value)| (model, value / typical[model] - 1.0))
+            .collect();
+
+        print!("  {:<32}", benchmark);
+        for model in &models {
+            let (Some(value), Some(deviation)) = (values.get(model), deviations.get(model)) else {
+                print!(" {:>26}", "-");
+                continue;
+            };
+
+            let others: Vec<f64> = deviations
+                .iter()
+                .filter(|(other, _)| *other != model)
+                .map(|(_, deviation)| *deviation)
+                .collect();
+            let relative = if others.is_empty() {
+                *deviation
+            } else {
+                deviation - others.iter().sum::<f64>() / others.len() as f64
+            };
+
+            let marker = if *deviation > threshold && relative > threshold {
+                flagged.push((*benchmark, *model, "under", relative));
+                "!"
+            } else if *deviation < -threshold && relative < -threshold {
+                flagged.push((*benchmark, *model, "over", relative));
+                "!"
+            } else {
+                " "
+            };
+
+            print!(
+                " {:>26}",
+                format!("{value:.6} ({:+.1}%){marker}", deviation * 100.0)
+            );
+        }
+        println!();
+    }
+
+    println!();
+    if flagged.is_empty() {
+        println!(
+            "No model is off by more than {}% on any benchmark.",
+            args.threshold
+        );
+    } else {
+        for (benchmark, model, direction, relative) in &flagged {
+            println!(
+                "  {model} {direction}-charges '{benchmark}' by {:.1}% relative to the other models",
+                relative.abs() * 100.0
+            );
+        }
+    }
+
+    println!();
+    println!(
+        "  {:<32} {:>10} {:>14} {:>10}",
+        "Cost model", "Benchmarks", "ns/gas", "Variation"
+    );
+    for model in &accuracy {
+        println!(
+            "  {:<32} {:>10} {:>14.6} {:>9.1}%",
+            model.name,
+            model.benchmarks,
+            model.ns_per_gas,
+            model.variation * 100.0
+        );
+    }
+    println!();
+
+    // Only models which were run on every benchmark can be fairly compared.
+    let best = accuracy
+        .iter()
+        .filter(|model| model.benchmarks == ns_per_gas.len() && model.benchmarks > 1)
+        .min_by(|lhs, rhs| lhs.variation.total_cmp(&rhs.variation));
+
+    match best {
+        Some(model) => println!(
+            "Best predictor of runtime: {} (the nanoseconds per gas vary by {:.1}%)",
+            model.name,
+            model.variation * 100.0
+        ),
+        None => println!(
+            "Not enough benchmarks were run under the same models to pick the best predictor."
+        ),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,732 @@
+use crate::host::{HostCallStats, HostFunctions, HostState};
+use crate::perf::{Counters, PerfCounters, PhaseCounters};
+use crate::stats::{summarize, Summary};
+use crate::Error;
+use polkavm::{
+    Config, Engine, InterruptKind, Module, ModuleConfig, ProgramBlob, ProgramCounter, RawInstance,
+    Reg,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+
+/// A program together with its input and how it should be measured.
+#[derive(Copy, Clone)]
+pub struct Benchmark<'a> {
+    pub blob: &'a ProgramBlob,
+    pub export: &'a str,
+    pub data: &'a [u8],
+    pub gas: i64,
+    pub warmup: u32,
+    pub iterations: u32,
+    pub reject_outliers: bool,
+    /// The hardware performance counters to collect around each phase, if any.
+    pub perf: Option<&'a PerfCounters>,
+    /// Whether to reuse a single instance and reset it between runs instead of
+    /// instantiating the module for every run.
+    pub reuse_instance: bool,
+    pub input_strategy: InputStrategy,
+}
+
+/// How the input is passed to the program.
+///
+/// With every strategy the program is called with the address of the input in A0 (zero when
+/// it isn't in memory) and its length in A1.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputStrategy {
+    /// Copy the input into the aux data region, which is sized to fit it.
+    #[default]
+    Aux,
+    /// Grow the heap with `sbrk` and copy the input into it.
+    Heap,
+    /// Copy the input to the end of the heap's address range, and then make its pages read-only.
+    /// This needs dynamic paging, so the program's other pages are loaded as they're accessed.
+    ReadOnly,
+    /// Don't copy the input up front; the program pulls it in chunks through the `read_input`
+    /// host call.
+    HostCall,
+}
+
+/// How long each phase of a single benchmark run took, in seconds.
+#[derive(Copy, Clone, Default, Debug, Serialize)]
+pub struct Phases {
+    pub engine: f64,
+    pub compile: f64,
+    pub instantiate: f64,
+    /// The time spent resetting a reused instance; zero when the instance was created anew.
+    pub reset: f64,
+    pub write_input: f64,
+    pub run: f64,
+}
+
+impl Phases {
+    pub fn as_array(&self) -> [f64; 6] {
+        [
+            self.engine,
+            self.compile,
+            self.instantiate,
+            self.reset,
+            self.write_input,
+            self.run,
+        ]
+    }
+
+    /// Returns the per-phase median of the given runs.
+    pub fn median(runs: &[Phases]) -> Phases {
+        let median = |get: fn(&Phases) -> f64| {
+            let samples: Vec<f64> = runs.iter().map(get).collect();
+            crate::stats::median(&samples)
+        };
+
+        Phases {
+            engine: median(|phases| phases.engine),
+            compile: median(|phases| phases.compile),
+            instantiate: median(|phases| phases.instantiate),
+            reset: median(|phases| phases.reset),
+            write_input: median(|phases| phases.write_input),
+            run: median(|phases| phases.run),
+        }
+    }
+
+    /// The time spent executing the program once it was compiled, including instantiation
+    /// or resetting the instance, and the setup of its input.
+    pub fn execution(&self) -> f64 {
+        self.instantiate + self.reset + self.write_input + self.run
+    }
+}
+
+/// Why the execution of a program didn't finish.
+#[derive(Debug)]
+pub enum FailureKind {
+    Trap,
+    NotEnoughGas,
+    Ecalli(u32),
+    HostCall(String),
+    Segfault { page_address: u32 },
+    Unexpected(InterruptKind),
+    Vm(String),
+}
+
+#[derive(Debug)]
+pub struct ExecutionFailure {
+    pub kind: FailureKind,
+    /// The program counter at the point of failure, if known.
+    pub program_counter: Option<ProgramCounter>,
+    pub gas_remaining: i64,
+}
+
+impl ExecutionFailure {
+    /// The exit code with which the process reports this failure.
+    pub fn exit_code(&self) -> i32 {
+        match self.kind {
+            FailureKind::Trap => 2,
+            FailureKind::NotEnoughGas => 3,
+            FailureKind::Ecalli(..) => 4,
+            FailureKind::Segfault { .. } => 5,
+            FailureKind::Unexpected(..) => 6,
+            FailureKind::Vm(..) => 7,
+            FailureKind::HostCall(..) => 8,
+        }
+    }
+}
+
+impl core::fmt::Display for ExecutionFailure {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.kind {
+            FailureKind::Trap => fmt.write_str("the program trapped")?,
+            FailureKind::NotEnoughGas => fmt.write_str("the program ran out of gas")?,
+            FailureKind::Ecalli(number) => write!(fmt, "unhandled host call: {number}")?,
+            FailureKind::HostCall(ref error) => write!(fmt, "host call failed: {error}")?,
+            FailureKind::Segfault { page_address } => {
+                write!(fmt, "segmentation fault at page 0x{page_address:x}")?
+            }
+            FailureKind::Unexpected(ref interrupt_kind) => {
+                write!(fmt, "unexpected interruption: {interrupt_kind:?}")?
+            }
+            FailureKind::Vm(ref error) => write!(fmt, "execution failed: {error}")?,
+        }
+
+        match self.program_counter {
+            Some(program_counter) => write!(fmt, " (pc = {program_counter}")?,
+            None => write!(fmt, " (pc = unknown")?,
+        }
+
+        write!(fmt, ", gas remaining = {})", self.gas_remaining)
+    }
+}
+
+pub struct Outcome {
+    pub result: u64,
+    pub gas_used: i64,
+    pub phases: Phases,
+    /// The hardware performance counters of each phase, if they were collected.
+    pub counters: Option<PhaseCounters>,
+    /// The data the program wrote to the output buffer.
+    pub output: Vec<u8>,
+    pub host_calls: BTreeMap<&'static str, HostCallStats>,
+}
+
+/// Measures the time and, optionally, the hardware performance counters of a single phase.
+struct PhaseTimer<'a> {
+    perf: Option<&'a PerfCounters>,
+    instant: Instant,
+}
+
+impl<'a> PhaseTimer<'a> {
+    fn start(perf: Option<&'a PerfCounters>) -> Self {
+        if let Some(perf) = perf {
+            perf.start();
+        }
+
+        PhaseTimer {
+            perf,
+            instant: Instant::now(),
+        }
+    }
+
+    /// Returns the elapsed time in seconds, and the counters.
+    fn stop(self) -> (f64, Counters) {
+        let elapsed = self.instant.elapsed().as_secs_f64();
+        let counters = self.perf.map(PerfCounters::stop).unwrap_or_default();
+        (elapsed, counters)
+    }
+}
+
+/// A program compiled under a single configuration, ready to be run with its input.
+///
+/// The engine and the module are created once. Every run then either instantiates the module
+/// anew or, when `reuse_instance` is set, resets the instance kept from the previous run.
+pub struct BenchmarkRunner<'a> {
+    benchmark: Benchmark<'a>,
+    engine: Engine,
+    module: Module,
+    entry_point: ProgramCounter,
+    host_functions: HostFunctions,
+    /// The instance kept between the runs when it's reused.
+    instance: Option<RawInstance>,
+    /// How long creating the engine and compiling the module took.
+    setup_phases: Phases,
+    setup_counters: PhaseCounters,
+}
+
+impl<'a> BenchmarkRunner<'a> {
+    /// Creates the engine and compiles the module. The cost model is the one set in the `config`.
+    pub fn new(
+        config: &Config,
+        module_config: &ModuleConfig,
+        benchmark: &Benchmark<'a>,
+    ) -> Result<Self, Error> {
+        let mut phases = Phases::default();
+        let mut counters = PhaseCounters::default();
+
+        // Memory can only be protected with dynamic paging.
+        let is_dynamic_paging = benchmark.input_strategy == InputStrategy::ReadOnly;
+        let mut config = config.clone();
+        if is_dynamic_paging {
+            config.set_allow_dynamic_paging(true);
+        }
+
+        let timer = PhaseTimer::start(benchmark.perf);
+        let engine = Engine::new(&config)
+            .map_err(|error| format!("failed to create the engine: {error}"))?;
+        (phases.engine, counters.engine) = timer.stop();
+
+        let timer = PhaseTimer::start(benchmark.perf);
+        // The aux data region is only needed when the input is passed through it.
+        let mut module_config = module_config.clone();
+        if benchmark.input_strategy != InputStrategy::Aux {
+            module_config.set_aux_data_size(0);
+        }
+        module_config.set_dynamic_paging(is_dynamic_paging);
+
+        let module = Module::from_blob(&engine, &module_config, benchmark.blob.clone())
+            .map_err(|error| format!("failed to compile the module: {error}"))?;
+        (phases.compile, counters.compile) = timer.stop();
+
+        let export = benchmark.export;
+        let entry_point = module
+            .exports()
+            .find(|symbol| symbol == export)
+            .ok_or_else(|| format!("export not found: '{export}'"))?
+            .program_counter();
+
+        Ok(BenchmarkRunner {
+            benchmark: *benchmark,
+            engine,
+            module,
+            entry_point,
+            host_functions: HostFunctions::new(benchmark.blob),
+            instance: None,
+            setup_phases: phases,
+            setup_counters: counters,
+        })
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Returns how long creating the engine and compiling the module took; the other phases are zero.
+    pub fn setup_phases(&self) -> Phases {
+        self.setup_phases
+    }
+
+    /// Returns the hardware performance counters of creating the engine and compiling the module,
+    /// if they were collected.
+    pub fn setup_counters(&self) -> Option<PhaseCounters> {
+        self.benchmark.perf.map(|_| self.setup_counters)
+    }
+
+    /// Runs the program once. The engine and compile phases of the outcome are zero.
+    ///
+    /// When the instance is reused only the first run instantiates the module, and every
+    /// later one resets the instance's memory and registers instead.
+    pub fn run_once(&mut self, echo_logs: bool) -> Result<Outcome, Error> {
+        self.run_impl(echo_logs, None)
+    }
+
+    /// Runs the program `count` times, without echoing its logs.
+    pub fn run_many(&mut self, count: u32) -> Result<Vec<Outcome>, Error> {
+        (0..count).map(|_| self.run_once(false)).collect()
+    }
+
+    /// Runs the program once, calling `on_step` before every executed instruction.
+    ///
+    /// Step tracing must be enabled in the module config the runner was created with.
+    pub fn run_traced(&mut self, on_step: &mut dyn FnMut(&RawInstance)) -> Result<Outcome, Error> {
+        self.run_impl(true, Some(on_step))
+    }
+
+    fn run_impl(
+        &mut self,
+        echo_logs: bool,
+        mut on_step: Option<&mut dyn FnMut(&RawInstance)>,
+    ) -> Result<Outcome, Error> {
+        let Benchmark {
+            data,
+            gas,
+            perf,
+            reuse_instance,
+            ..
+        } = self.benchmark;
+        let module = &self.module;
+
+        let mut phases = Phases::default();
+        let mut counters = PhaseCounters::default();
+
+        let timer = PhaseTimer::start(perf);
+        let (mut instance, is_reused) = match self.instance.take() {
+            Some(mut instance) => {
+                instance
+                    .reset_memory()
+                    .map_err(|error| format!("failed to reset the instance: {error}"))?;
+                for reg in Reg::ALL {
+                    instance.set_reg(reg, 0);
+                }
+                (instance, true)
+            }
+            None => {
+                let instance = module
+                    .instantiate()
+                    .map_err(|error| format!("failed to instantiate the module: {error}"))?;
+                (instance, false)
+            }
+        };
+        instance.set_next_program_counter(self.entry_point);
+        instance.set_reg(Reg::RA, polkavm::RETURN_TO_HOST);
+        instance.set_reg(Reg::SP, module.default_sp());
+        instance.set_gas(gas);
+        if is_reused {
+            (phases.reset, counters.reset) = timer.stop();
+        } else {
+            (phases.instantiate, counters.instantiate) = timer.stop();
+        }
+
+        // The pages populated during this run; with dynamic paging a fault on one of these
+        // is a write to read-only memory rather than a first access.
+        let mut loaded_pages = HashSet::new();
+
+        let timer = PhaseTimer::start(perf);
+        let address = self.write_input(&mut instance, &mut loaded_pages)?;
+        instance.set_reg(Reg::A0, u64::from(address));
+        instance.set_reg(Reg::A1, data.len() as u64);
+        (phases.write_input, counters.write_input) = timer.stop();
+
+        let mut host_state = HostState::new(data, echo_logs);
+
+        let timer = PhaseTimer::start(perf);
+        let kind = loop {
+            let kind = match instance.run() {
+                Ok(InterruptKind::Finished) => break None,
+                Ok(InterruptKind::Ecalli(number)) => match self.host_functions.get(number) {
+                    Some(function) => match host_state.call(function, &mut instance) {
+                        Ok(()) => continue,
+                        Err(error) => FailureKind::HostCall(error),
+                    },
+                    None => FailureKind::Ecalli(number),
+                },
+                Ok(InterruptKind::Step) => match on_step {
+                    Some(ref mut on_step) => {
+                        on_step(&instance);
+                        continue;
+                    }
+                    None => FailureKind::Unexpected(InterruptKind::Step),
+                },
+                Ok(InterruptKind::Trap) => FailureKind::Trap,
+                Ok(InterruptKind::NotEnoughGas) => FailureKind::NotEnoughGas,
+                Ok(InterruptKind::Segfault(segfault))
+                    if self.benchmark.input_strategy == InputStrategy::ReadOnly =>
+                {
+                    match self.load_page(&mut instance, &mut loaded_pages, segfault.page_address) {
+                        Ok(true) => continue,
+                        Ok(false) => FailureKind::Segfault {
+                            page_address: segfault.page_address,
+                        },
+                        Err(error) => FailureKind::Vm(error),
+                    }
+                }
+                Ok(InterruptKind::Segfault(segfault)) => FailureKind::Segfault {
+                    page_address: segfault.page_address,
+                },
+                Err(error) => FailureKind::Vm(error.to_string()),
+            };
+
+            break Some(kind);
+        };
+        (phases.run, counters.run) = timer.stop();
+
+        let failure = kind.map(|kind| ExecutionFailure {
+            kind,
+            program_counter: instance.program_counter(),
+            gas_remaining: instance.gas(),
+        });
+        let result = instance.reg(Reg::A0);
+        let gas_used = gas - instance.gas();
+
+        if reuse_instance {
+            self.instance = Some(instance);
+        }
+
+        if let Some(failure) = failure {
+            return Err(failure.into());
+        }
+
+        Ok(Outcome {
+            result,
+            gas_used,
+            phases,
+            counters: perf.map(|_| counters),
+            output: host_state.output,
+            host_calls: host_state.stats,
+        })
+    }
+
+    /// Passes the input to the program according to the input strategy, and returns its address.
+    ///
+    /// The pages of a read-only input are added to `loaded_pages`.
+    fn write_input(
+        &self,
+        instance: &mut RawInstance,
+        loaded_pages: &mut HashSet<u32>,
+    ) -> Result<u32, String> {
+        let data = self.benchmark.data;
+        let memory_map = self.module.memory_map();
+        let length = u32::try_from(data.len())
+            .map_err(|_| format!("the input is too big: {} bytes", data.len()))?;
+        let too_big = || {
+            format!(
+                "the input doesn't fit into the heap: {length} bytes, with a maximum heap size of {} bytes",
+                memory_map.max_heap_size()
+            )
+        };
+
+        let address = match self.benchmark.input_strategy {
+            InputStrategy::Aux => memory_map.aux_data_address(),
+            InputStrategy::Heap => {
+                let address = memory_map.heap_base() + instance.heap_size();
+                let grown = instance
+                    .sbrk(length)
+                    .map_err(|error| format!("failed to grow the heap: {error}"))?;
+                if grown.is_none() {
+                    return Err(too_big());
+                }
+
+                address
+            }
+            InputStrategy::ReadOnly => {
+                // With dynamic paging the heap can't be grown from the host, so the input is put
+                // at the very end of the heap's address range, where the program's own allocations
+                // are least likely to reach. Only whole pages can be protected.
+                let page_size = memory_map.page_size();
+                let heap_end = memory_map.heap_base() + memory_map.max_heap_size();
+                let address = heap_end
+                    .checked_sub(length.next_multiple_of(page_size))
+                    .map(|address| address / page_size * page_size)
+                    .filter(|&address| address >= memory_map.heap_base())
+                    .ok_or_else(too_big)?;
+
+                instance
+                    .write_memory(address, data)
+                    .map_err(|error| format!("failed to write the input: {error}"))?;
+                let size = length.next_multiple_of(page_size);
+                instance
+                    .protect_memory(address, size)
+                    .map_err(|error| format!("failed to make the input read-only: {error}"))?;
+                loaded_pages.extend((address..address + size).step_by(page_size as usize));
+                return Ok(address);
+            }
+            InputStrategy::HostCall => return Ok(0),
+        };
+
+        instance
+            .write_memory(address, data)
+            .map_err(|error| format!("failed to write the input: {error}"))?;
+
+        Ok(address)
+    }
+
+    /// Populates a page of the program's memory on its first access, which is necessary with
+    /// dynamic paging. Returns `false` if the page isn't part of the program's memory, or if
+    /// it was already populated, in which case the program tried to write to read-only memory.
+    fn load_page(
+        &self,
+        instance: &mut RawInstance,
+        loaded_pages: &mut HashSet<u32>,
+        page_address: u32,
+    ) -> Result<bool, String> {
+        if loaded_pages.contains(&page_address) {
+            return Ok(false);
+        }
+
+        let memory_map = self.module.memory_map();
+        let page_size = memory_map.page_size();
+        let blob = self.benchmark.blob;
+
+        let contains =
+            |address: u32, size: u32| page_address >= address && page_address - address < size;
+        let page_of = |data: &[u8], address: u32| {
+            let mut page = vec![0; page_size as usize];
+            let chunk = data
+                .get((page_address - address) as usize..)
+                .unwrap_or_default();
+            let length = chunk.len().min(page.len());
+            page[..length].copy_from_slice(&chunk[..length]);
+            page
+        };
+
+        let result = if contains(memory_map.ro_data_address(), memory_map.ro_data_size()) {
+            let page = page_of(blob.ro_data(), memory_map.ro_data_address());
+            instance
+                .write_memory(page_address, &page)
+                .and_then(|()| instance.protect_memory(page_address, page_size))
+        } else if contains(memory_map.rw_data_address(), memory_map.rw_data_size()) {
+            let page = page_of(blob.rw_data(), memory_map.rw_data_address());
+            instance.write_memory(page_address, &page)
+        } else if contains(memory_map.heap_base(), memory_map.max_heap_size())
+            || contains(memory_map.stack_address_low(), memory_map.stack_size())
+        {
+            instance.zero_memory(page_address, page_size)
+        } else {
+            return Ok(false);
+        };
+
+        result
+            .map_err(|error| format!("failed to load the page at 0x{page_address:x}: {error}"))?;
+        loaded_pages.insert(page_address);
+        Ok(true)
+    }
+
+    /// Runs the benchmark once, then runs the warmup iterations, and then the timed ones.
+    ///
+    /// The phases of the initial run include creating the engine and compiling the module.
+    pub fn measure(&mut self) -> Result<Measurement, Error> {
+        let initial = self.run_once(true)?;
+        self.run_many(self.benchmark.warmup)?;
+
+        let mut phases = Vec::new();
+        let mut counters = Vec::new();
+        for outcome in self.run_many(self.benchmark.iterations)? {
+            phases.push(outcome.phases);
+            counters.extend(outcome.counters);
+        }
+
+        let timings: Vec<f64> = phases.iter().map(|phases| phases.execution()).collect();
+        let summary = summarize(&timings, self.benchmark.reject_outliers);
+
+        Ok(Measurement {
+            result: initial.result,
+            gas_used: initial.gas_used,
+            output: initial.output,
+            host_calls: initial.host_calls,
+            initial_phases: Phases {
+                engine: self.setup_phases.engine,
+                compile: self.setup_phases.compile,
+                ..initial.phases
+            },
+            phases,
+            counters,
+            timings,
+            summary,
+        })
+    }
+}
+
+pub struct Measurement {
+    pub result: u64,
+    pub gas_used: i64,
+    /// The data the program wrote to the output buffer during the initial run.
+    pub output: Vec<u8>,
+    /// The host calls made during the initial run.
+    pub host_calls: BTreeMap<&'static str, HostCallStats>,
+    pub initial_phases: Phases,
+    pub phases: Vec<Phases>,
+    /// The hardware performance counters of each timed iteration, if they were collected.
+    pub counters: Vec<PhaseCounters>,
+    /// The execution time of each timed iteration, in seconds.
+    pub timings: Vec<f64>,
+    pub summary: Option<Summary>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polkavm::program::Instruction;
+    use polkavm::{BackendKind, GasMeteringKind};
+    use polkavm_common::program::asm;
+    use polkavm_common::writer::ProgramBlobBuilder;
+
+    const INPUT: [u8; 3] = [3, 7, 9];
+
+    /// The read-only data always starts right after the first, inaccessible 64 KiB.
+    const RO_DATA_ADDRESS: u32 = 0x10000;
+
+    /// Builds a program with a single "run" export and a few bytes of read-only data.
+    fn build_program(code: &[Instruction]) -> ProgramBlob {
+        let mut builder = ProgramBlobBuilder::new_64bit();
+        builder.set_stack_size(4096);
+        builder.set_ro_data_size(16);
+        builder.set_ro_data(vec![1; 16]);
+        builder.add_export_by_basic_block(0, b"run");
+        builder.set_code(code, &[]);
+        ProgramBlob::parse(builder.into_vec().unwrap().into()).unwrap()
+    }
+
+    /// Creates a runner which passes the input to the program as read-only memory.
+    fn runner(blob: &ProgramBlob) -> BenchmarkRunner<'_> {
+        let mut config = Config::new();
+        config.set_backend(Some(BackendKind::Interpreter));
+        let module_config = crate::module_config(Some(GasMeteringKind::Sync), &INPUT).unwrap();
+        let benchmark = Benchmark {
+            blob,
+            export: "run",
+            data: &INPUT,
+            // Enough for the program, but not for retrying a faulting store forever.
+            gas: 1_000_000,
+            warmup: 0,
+            iterations: 0,
+            reject_outliers: false,
+            perf: None,
+            reuse_instance: false,
+            input_strategy: InputStrategy::ReadOnly,
+        };
+
+        BenchmarkRunner::new(&config, &module_config, &benchmark).unwrap()
+    }
+
+    /// Checks that the run failed on a write to the given read-only page. The interpreter
+    /// reports that as a trap, and the compiler as a segfault.
+    fn assert_write_fault(result: Result<Outcome, Error>, expected_page_address: u32) {
+        match result {
+            Err(Error::Execution(ExecutionFailure {
+                kind: FailureKind::Segfault { page_address },
+                ..
+            })) => assert_eq!(page_address, expected_page_address),
+            Err(Error::Execution(ExecutionFailure {
+                kind: FailureKind::Trap,
+                ..
+            })) => {}
+            Err(error) => panic!("unexpected error: {error}"),
+            Ok(outcome) => panic!("unexpected success: 0x{:x}", outcome.result),
+        }
+    }
+
+    fn input_address(runner: &BenchmarkRunner) -> u32 {
+        let memory_map = runner.module().memory_map();
+        memory_map.heap_base() + memory_map.max_heap_size() - memory_map.page_size()
+    }
+
+    #[test]
+    fn read_only_input_can_be_read() {
+        let blob = build_program(&[
+            asm::load_indirect_u8(Reg::A0, Reg::A0, 1),
+            asm::jump_indirect(Reg::RA, 0),
+        ]);
+
+        let outcome = runner(&blob).run_once(false).unwrap();
+        assert_eq!(outcome.result, 7);
+    }
+
+    #[test]
+    fn writing_to_the_read_only_input_fails() {
+        let blob = build_program(&[
+            asm::store_indirect_u8(Reg::A1, Reg::A0, 0),
+            asm::jump_indirect(Reg::RA, 0),
+        ]);
+
+        let mut runner = runner(&blob);
+        let input_address = input_address(&runner);
+        assert_write_fault(runner.run_once(false), input_address);
+    }
+
+    #[test]
+    fn writing_to_the_read_only_data_fails() {
+        let blob = build_program(&[
+            asm::store_u8(Reg::A1, RO_DATA_ADDRESS),
+            asm::jump_indirect(Reg::RA, 0),
+        ]);
+
+        let mut runner = runner(&blob);
+        assert_eq!(
+            runner.module().memory_map().ro_data_address(),
+            RO_DATA_ADDRESS
+        );
+        assert_write_fault(runner.run_once(false), RO_DATA_ADDRESS);
+    }
+
+    #[test]
+    fn populated_pages_are_not_loaded_again() {
+        let blob = build_program(&[asm::jump_indirect(Reg::RA, 0)]);
+        let runner = runner(&blob);
+        let mut instance = runner.module().instantiate().unwrap();
+        let mut loaded_pages = HashSet::new();
+
+        let address = runner
+            .write_input(&mut instance, &mut loaded_pages)
+            .unwrap();
+        assert_eq!(address, input_address(&runner));
+        assert_eq!(
+            runner.load_page(&mut instance, &mut loaded_pages, address),
+            Ok(false)
+        );
+
+        assert_eq!(
+            runner.load_page(&mut instance, &mut loaded_pages, RO_DATA_ADDRESS),
+            Ok(true)
+        );
+        assert_eq!(
+            runner.load_page(&mut instance, &mut loaded_pages, RO_DATA_ADDRESS),
+            Ok(false)
+        );
+
+        // The first 64 KiB are never part of the program's memory.
+        assert_eq!(
+            runner.load_page(&mut instance, &mut loaded_pages, 0),
+            Ok(false)
+        );
+    }
+}
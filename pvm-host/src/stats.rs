@@ -0,0 +1,280 @@
+use serde::Serialize;
+
+/// Summary statistics of a set of timing samples, in seconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    /// The number of samples left after outlier rejection.
+    pub samples: usize,
+    /// The indices of the samples which were rejected as outliers.
+    pub outliers: Vec<usize>,
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    /// The lower bound of the 95% confidence interval of the mean.
+    pub ci_low: f64,
+    /// The upper bound of the 95% confidence interval of the mean.
+    pub ci_high: f64,
+}
+
+/// Two-sided 95% critical values of Student's t-distribution for 1..=30 degrees of freedom.
+const T_TABLE_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+fn t_critical_95(degrees_of_freedom: usize) -> f64 {
+    match degrees_of_freedom {
+        0 => f64::NAN,
+        1..=30 => T_TABLE_95[degrees_of_freedom - 1],
+        31..=60 => 2.000,
+        61..=120 => 1.980,
+        _ => 1.960,
+    }
+}
+
+/// Returns the given quantile of already sorted samples, linearly interpolating between them.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * fraction
+}
+
+/// Returns the median of the given samples.
+pub fn median(samples: &[f64]) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    quantile(&sorted, 0.5)
+}
+
+/// Returns the indices of the samples lying outside of Tukey's fences (1.5 IQR beyond the quartiles).
+fn find_outliers(samples: &[f64]) -> Vec<usize> {
+    // Quartiles of less than four samples aren't meaningful.
+    if samples.len() < 4 {
+        return Vec::new();
+    }
+
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let q1 = quantile(&sorted, 0.25);
+    let q3 = quantile(&sorted, 0.75);
+    let iqr = q3 - q1;
+    let low = q1 - 1.5 * iqr;
+    let high = q3 + 1.5 * iqr;
+
+    samples
+        .iter()
+        .enumerate()
+        .filter(|(_, &sample)| sample < low || sample > high)
+        .map(|(nth, _)| nth)
+        .collect()
+}
+
+/// Computes the summary statistics of the given samples, optionally rejecting outliers first.
+pub fn summarize(samples: &[f64], reject_outliers: bool) -> Option<Summary> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let outliers = if reject_outliers {
+        find_outliers(samples)
+    } else {
+        Vec::new()
+    };
+
+    let mut sorted: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .filter(|(nth, _)| !outliers.contains(nth))
+        .map(|(_, &sample)| sample)
+        .collect();
+    sorted.sort_by(f64::total_cmp);
+
+    let count = sorted.len();
+    let mean = sorted.iter().sum::<f64>() / count as f64;
+    let stddev = if count > 1 {
+        let variance = sorted
+            .iter()
+            .map(|sample| (sample - mean) * (sample - mean))
+            .sum::<f64>()
+            / (count - 1) as f64;
+        variance.sqrt()
+    } else {
+        0.0
+    };
+
+    let margin = if count > 1 {
+        t_critical_95(count - 1) * stddev / (count as f64).sqrt()
+    } else {
+        0.0
+    };
+
+    Some(Summary {
+        samples: count,
+        outliers,
+        min: sorted[0],
+        max: sorted[count - 1],
+        median: quantile(&sorted, 0.5),
+        mean,
+        stddev,
+        ci_low: mean - margin,
+        ci_high: mean + margin,
+    })
+}
+
+/// Fits a line through the given `(x, y)` points using ordinary least squares.
+///
+/// Returns the slope and the intercept, or `None` if the `x` values don't vary.
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points
+        .iter()
+        .map(|(x, _)| (x - mean_x) * (x - mean_x))
+        .sum();
+    if variance == 0.0 || !variance.is_finite() {
+        return None;
+    }
+
+    let slope = covariance / variance;
+    Some((slope, mean_y - slope * mean_x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_of_no_samples_is_nan() {
+        assert!(quantile(&[], 0.5).is_nan());
+    }
+
+    #[test]
+    fn quantile_of_one_sample_is_that_sample() {
+        assert_eq!(quantile(&[3.0], 0.0), 3.0);
+        assert_eq!(quantile(&[3.0], 0.5), 3.0);
+        assert_eq!(quantile(&[3.0], 1.0), 3.0);
+    }
+
+    #[test]
+    fn quantile_interpolates_between_samples() {
+        let sorted = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(quantile(&sorted, 0.0), 1.0);
+        assert_eq!(quantile(&sorted, 0.25), 1.75);
+        assert_eq!(quantile(&sorted, 0.5), 2.5);
+        assert_eq!(quantile(&sorted, 1.0), 4.0);
+    }
+
+    #[test]
+    fn median_of_odd_and_even_lengths() {
+        assert_eq!(median(&[5.0, 1.0, 3.0]), 3.0);
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+
+    #[test]
+    fn find_outliers_needs_four_samples() {
+        assert!(find_outliers(&[]).is_empty());
+        assert!(find_outliers(&[1.0]).is_empty());
+        assert!(find_outliers(&[1.0, 1.0, 100.0]).is_empty());
+    }
+
+    #[test]
+    fn find_outliers_returns_the_indices_outside_of_the_fences() {
+        let samples = [1.0, 1.1, 100.0, 0.9, 1.0, 1.05, -50.0];
+        assert_eq!(find_outliers(&samples), vec![2, 6]);
+        assert!(find_outliers(&[1.0, 2.0, 3.0, 4.0]).is_empty());
+    }
+
+    #[test]
+    fn summarize_no_samples() {
+        assert!(summarize(&[], true).is_none());
+    }
+
+    #[test]
+    fn summarize_one_sample() {
+        let summary = summarize(&[2.0], true).unwrap();
+        assert_eq!(summary.samples, 1);
+        assert!(summary.outliers.is_empty());
+        assert_eq!(summary.min, 2.0);
+        assert_eq!(summary.max, 2.0);
+        assert_eq!(summary.median, 2.0);
+        assert_eq!(summary.mean, 2.0);
+        assert_eq!(summary.stddev, 0.0);
+        assert_eq!(summary.ci_low, 2.0);
+        assert_eq!(summary.ci_high, 2.0);
+    }
+
+    #[test]
+    fn summarize_odd_and_even_lengths() {
+        let summary = summarize(&[3.0, 1.0, 2.0], false).unwrap();
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.median, 2.0);
+        assert_eq!(summary.mean, 2.0);
+        assert_eq!(summary.stddev, 1.0);
+        let margin = 4.303 / 3.0_f64.sqrt();
+        assert!((summary.ci_low - (2.0 - margin)).abs() < 1e-12);
+        assert!((summary.ci_high - (2.0 + margin)).abs() < 1e-12);
+
+        let summary = summarize(&[4.0, 1.0, 3.0, 2.0], false).unwrap();
+        assert_eq!(summary.samples, 4);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 4.0);
+        assert_eq!(summary.median, 2.5);
+        assert_eq!(summary.mean, 2.5);
+    }
+
+    #[test]
+    fn summarize_rejects_outliers_only_when_asked() {
+        let samples = [1.0, 1.1, 100.0, 0.9, 1.0];
+
+        let summary = summarize(&samples, true).unwrap();
+        assert_eq!(summary.samples, 4);
+        assert_eq!(summary.outliers, vec![2]);
+        assert_eq!(summary.max, 1.1);
+
+        let summary = summarize(&samples, false).unwrap();
+        assert_eq!(summary.samples, 5);
+        assert!(summary.outliers.is_empty());
+        assert_eq!(summary.max, 100.0);
+    }
+
+    #[test]
+    fn linear_fit_of_no_points() {
+        assert_eq!(linear_fit(&[]), None);
+    }
+
+    #[test]
+    fn linear_fit_of_one_point() {
+        assert_eq!(linear_fit(&[(1.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn linear_fit_of_a_line() {
+        let points = [(1.0, 5.0), (2.0, 7.0), (3.0, 9.0), (4.0, 11.0)];
+        assert_eq!(linear_fit(&points), Some((2.0, 3.0)));
+    }
+
+    #[test]
+    fn linear_fit_of_a_constant_series() {
+        let points = [(1.0, 4.0), (2.0, 4.0), (3.0, 4.0)];
+        assert_eq!(linear_fit(&points), Some((0.0, 4.0)));
+
+        let points = [(2.0, 1.0), (2.0, 2.0), (2.0, 3.0)];
+        assert_eq!(linear_fit(&points), None);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::analysis::{opcode_name, opcodes_by_offset};
+use crate::{InputArgs, InputStrategyArgs};
+use polkavm::program::Opcode;
+use polkavm::{BackendKind, GasMeteringKind};
+use pvm_host::runner::{Benchmark, BenchmarkRunner};
+use pvm_host::{config_with_cost_model, module_config, Error};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+#[derive(clap::Args, Debug)]
+pub struct TraceArgs {
+    #[clap(flatten)]
+    inputs: InputArgs,
+
+    #[clap(flatten)]
+    strategy: InputStrategyArgs,
+
+    /// Write the histogram to this file as JSON.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// How many times a given instruction was executed, and how much gas it accounted for.
+#[derive(Clone, Debug, Serialize)]
+pub struct OpcodeStats {
+    pub name: String,
+    pub count: u64,
+    /// The gas charged for this instruction under each cost model.
+    pub gas: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Histogram {
+    pub benchmark: String,
+    pub instructions_executed: u64,
+    /// Sorted by the number of executions, in descending order.
+    pub opcodes: Vec<OpcodeStats>,
+    /// The total gas accounted for by the histogram under each cost model.
+    pub total_gas: BTreeMap<String, u64>,
+}
+
+pub fn main_trace(args: TraceArgs) -> Result<(), Error> {
+    let inputs = args.inputs.load()?;
+    let benchmark = Benchmark {
+        input_strategy: args.strategy.input_strategy.into(),
+        ..args.inputs.single_run(&inputs)
+    };
+
+    // The number of times each instruction is executed doesn't depend on the cost model,
+    // so it's enough to trace the program once and then apply every model to the counts.
+    let mut config = config_with_cost_model(&inputs.cost_models[0].cost_model)?;
+    config.set_backend(Some(BackendKind::Interpreter));
+
+    let mut module_config = module_config(Some(GasMeteringKind::Sync), &inputs.data)?;
+    module_config.set_step_tracing(true);
+
+    let opcodes = opcodes_by_offset(&inputs.blob);
+    let mut counts: HashMap<Opcode, u64> = HashMap::new();
+    let mut unknown_count = 0;
+
+    println!("Tracing...");
+    let mut runner = BenchmarkRunner::new(&config, &module_config, &benchmark)?;
+    let outcome = runner.run_traced(&mut |instance| {
+        let opcode = instance
+            .program_counter()
+            .and_then(|program_counter| opcodes.get(&program_counter));
+
+        match opcode {
+            Some(opcode) => *counts.entry(*opcode).or_default() += 1,
+            None => unknown_count += 1,
+        }
+    })?;
+
+    println!("Result: 0x{:x}", outcome.result);
+    if unknown_count > 0 {
+        println!("WARNING: {unknown_count} step(s) at an unknown program counter were ignored");
+    }
+
+    let mut opcode_stats: Vec<OpcodeStats> = counts
+        .into_iter()
+        .map(|(opcode, count)| {
+            let gas = inputs
+                .cost_models
+                .iter()
+                .map(|cost_model| {
+                    let weight = cost_model.cost_model.cost_for_opcode(opcode);
+                    (cost_model.name.clone(), count * u64::from(weight))
+                })
+                .collect();
+
+            OpcodeStats {
+                name: opcode_name(opcode),
+                count,
+                gas,
+            }
+        })
+        .collect();
+    opcode_stats.sort_by(|lhs, rhs| rhs.count.cmp(&lhs.count).then(lhs.name.cmp(&rhs.name)));
+
+    let instructions_executed = opcode_stats.iter().map(|stats| stats.count).sum();
+    let total_gas: BTreeMap<String, u64> = inputs
+        .cost_models
+        .iter()
+        .map(|cost_model| {
+            let model = &cost_model.name;
+            let total = opcode_stats.iter().map(|stats| stats.gas[model]).sum();
+            (model.clone(), total)
+        })
+        .collect();
+
+    let histogram = Histogram {
+        benchmark: args.inputs.default_name(),
+        instructions_executed,
+        opcodes: opcode_stats,
+        total_gas,
+    };
+
+    print_histogram(&histogram);
+
+    if let Some(path) = args.output {
+        let json = serde_json::to_string_pretty(&histogram)
+            .map_err(|error| format!("failed to serialize the histogram: {error}"))?;
+        std::fs::write(&path, json)
+            .map_err(|error| format!("failed to write {path:?}: {error}"))?;
+    }
+
+    Ok(())
+}
+
+/// Returns how many percent of the `total` the `value` is, or zero if the total is zero.
+pub fn percent(value: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        value as f64 / total as f64 * 100.0
+    }
+}
+
+fn print_histogram(histogram: &Histogram) {
+    println!("Instructions executed: {}", histogram.instructions_executed);
+    println!();
+
+    print!("  {:<40} {:>14} {:>7}", "Opcode", "Count", "%");
+    for model in histogram.total_gas.keys() {
+        print!(" {:>16} {:>7}", format!("{model} gas"), "%");
+    }
+    println!();
+
+    for stats in &histogram.opcodes {
+        print!(
+            "  {:<40} {:>14} {:>6.2}%",
+            stats.name,
+            stats.count,
+            percent(stats.count, histogram.instructions_executed)
+        );
+        for (model, total) in &histogram.total_gas {
+            let gas = stats.gas[model];
+            print!(" {:>16} {:>6.2}%", gas, percent(gas, *total));
+        }
+        println!();
+    }
+
+    print!(
+        "  {:<40} {:>14} {:>7}",
+        "Total", histogram.instructions_executed, ""
+    );
+    for total in histogram.total_gas.values() {
+        print!(" {:>16} {:>7}", total, "");
+    }
+    println!();
+}